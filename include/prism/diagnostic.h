@@ -54,6 +54,16 @@ typedef enum {
  */
 PRISM_EXPORTED_FUNCTION const char * pm_diagnostic_type(const pm_diagnostic_t *diagnostic) PRISM_NONNULL(1);
 
+/**
+ * Get the ID of the given diagnostic.
+ *
+ * @param diagnostic The diagnostic to get the ID of.
+ * @returns The ID of the given diagnostic. Errors and then warnings are
+ *     numbered in the order that they appear in config.yml, which is the same
+ *     ID that is used in the serialization format.
+ */
+PRISM_EXPORTED_FUNCTION uint32_t pm_diagnostic_id(const pm_diagnostic_t *diagnostic) PRISM_NONNULL(1);
+
 /**
  * Get the location of the given diagnostic.
  *
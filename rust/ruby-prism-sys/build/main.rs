@@ -139,7 +139,10 @@ fn generate_bindings(ruby_include_path: &Path) -> bindgen::Bindings {
         .allowlist_type("pm_node_type")
         .allowlist_type("pm_options_t")
         .allowlist_type("pm_options_scope_t")
+        .allowlist_type("pm_source_init_result_t")
         .allowlist_type("pm_string_t")
+        .allowlist_type("pm_token_t")
+        .allowlist_type("pm_token_type")
         .allowlist_type("pm_warning_level_t")
         .allowlist_type(r"^pm_\w+_node_t")
         .allowlist_type(r"^pm_\w+_flags")
@@ -148,19 +151,26 @@ fn generate_bindings(ruby_include_path: &Path) -> bindgen::Bindings {
         .rustified_non_exhaustive_enum("pm_error_level_t")
         .rustified_non_exhaustive_enum(r"pm_\w+_flags")
         .rustified_non_exhaustive_enum("pm_node_type")
+        .rustified_non_exhaustive_enum("pm_token_type")
         .rustified_non_exhaustive_enum("pm_warning_level_t")
         // Functions
         .allowlist_function("pm_arena_free")
         .allowlist_function("pm_arena_new")
+        .allowlist_function("pm_buffer_free")
+        .allowlist_function("pm_buffer_length")
+        .allowlist_function("pm_buffer_new")
+        .allowlist_function("pm_buffer_value")
         .allowlist_function("pm_comment_location")
         .allowlist_function("pm_comment_type")
         .allowlist_function("pm_constant_length")
         .allowlist_function("pm_constant_start")
         .allowlist_function("pm_diagnostic_error_level")
+        .allowlist_function("pm_diagnostic_id")
         .allowlist_function("pm_diagnostic_location")
         .allowlist_function("pm_diagnostic_message")
         .allowlist_function("pm_diagnostic_type")
         .allowlist_function("pm_diagnostic_warning_level")
+        .allowlist_function("pm_dump_json")
         .allowlist_function("pm_line_offset_list_line_column")
         .allowlist_function("pm_magic_comment_key")
         .allowlist_function("pm_magic_comment_value")
@@ -180,17 +190,23 @@ fn generate_bindings(ruby_include_path: &Path) -> bindgen::Bindings {
         .allowlist_function("pm_options_scope_mut")
         .allowlist_function("pm_options_scopes_init")
         .allowlist_function("pm_options_version_set")
+        .allowlist_function("pm_node_type")
         .allowlist_function("pm_parse")
+        .allowlist_function("pm_parse_stream")
         .allowlist_function("pm_parser_comments_each")
         .allowlist_function("pm_parser_comments_size")
         .allowlist_function("pm_parser_constant")
         .allowlist_function("pm_parser_constants_each")
         .allowlist_function("pm_parser_constants_size")
         .allowlist_function("pm_parser_data_loc")
+        .allowlist_function("pm_parser_end")
         .allowlist_function("pm_parser_errors_each")
         .allowlist_function("pm_parser_errors_size")
+        .allowlist_function("pm_parser_filepath")
         .allowlist_function("pm_parser_free")
         .allowlist_function("pm_parser_frozen_string_literal")
+        .allowlist_function("pm_parser_lex_callback_set")
+        .allowlist_function("pm_parser_lex_state")
         .allowlist_function("pm_parser_line_offsets")
         .allowlist_function("pm_parser_magic_comments_each")
         .allowlist_function("pm_parser_magic_comments_size")
@@ -199,15 +215,26 @@ fn generate_bindings(ruby_include_path: &Path) -> bindgen::Bindings {
         .allowlist_function("pm_parser_start_line")
         .allowlist_function("pm_parser_warnings_each")
         .allowlist_function("pm_parser_warnings_size")
+        .allowlist_function("pm_prettyprint")
+        .allowlist_function("pm_serialize")
         .allowlist_function("pm_size_to_native")
+        .allowlist_function("pm_source_free")
+        .allowlist_function("pm_source_length")
+        .allowlist_function("pm_source_mapped_new")
+        .allowlist_function("pm_source_source")
+        .allowlist_function("pm_source_stream_new")
         .allowlist_function("pm_string_constant_init")
         .allowlist_function("pm_string_length")
         .allowlist_function("pm_string_source")
         .allowlist_function("pm_version")
         // Vars
         .allowlist_var(r"^pm_encoding\S+")
+        .allowlist_var(r"^PM_NODE_FLAG_\w+")
         .allowlist_var(r"^PM_OPTIONS_COMMAND_LINE_\w+")
         .allowlist_var(r"^PM_OPTIONS_SCOPE_FORWARDING_\w+")
+        .allowlist_var("PRISM_VERSION_MAJOR")
+        .allowlist_var("PRISM_VERSION_MINOR")
+        .allowlist_var("PRISM_VERSION_PATCH")
         .generate()
         .expect("Unable to generate prism bindings")
 }
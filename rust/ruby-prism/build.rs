@@ -91,10 +91,20 @@ struct Node {
     comment: String,
 }
 
+#[derive(Debug, Deserialize)]
+struct Token {
+    name: String,
+
+    comment: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct Config {
     nodes: Vec<Node>,
     flags: Vec<Flags>,
+    tokens: Vec<Token>,
+    errors: Vec<String>,
+    warnings: Vec<String>,
 }
 
 /// The main function for the build script. This will be run by Cargo when
@@ -109,6 +119,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
 
     let config: Config = serde_json::from_reader(config_file)?;
     write_bindings(&config)?;
+    write_deserialize(&config)?;
 
     Ok(())
 }
@@ -194,6 +205,37 @@ fn accessor_func_name(value: &str) -> String {
     result
 }
 
+/// Returns the name of the Rust enum variant from the given token or
+/// diagnostic name.
+fn variant_name(name: &str) -> String {
+    let mut result = String::with_capacity(name.len());
+
+    for part in name.split('_').filter(|part| !part.is_empty()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            result.extend(first.to_uppercase());
+            result.extend(chars.flat_map(char::to_lowercase));
+        }
+    }
+
+    result
+}
+
+/// Escapes the characters in a token comment that would otherwise be
+/// interpreted as markdown in the generated documentation.
+fn escape_markdown(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+
+    for char in text.chars() {
+        if matches!(char, '`' | '*' | '_' | '[' | ']' | '#' | '<' | '>' | '|' | '~' | '\\') {
+            result.push('\\');
+        }
+        result.push(char);
+    }
+
+    result
+}
+
 /// Write the generated struct for the node to the file.
 fn write_node(file: &mut File, flags: &[Flags], node: &Node) -> Result<(), Box<dyn std::error::Error>> {
     let mut example = false;
@@ -229,6 +271,35 @@ fn write_node(file: &mut File, flags: &[Flags], node: &Node) -> Result<(), Box<d
     writeln!(file, "    marker: PhantomData<&'pr mut pm{}_t>", struct_name(&node.name))?;
     writeln!(file, "}}")?;
     writeln!(file)?;
+    writeln!(file, "impl<'pr> TryFrom<Node<'pr>> for {}<'pr> {{", node.name)?;
+    writeln!(file, "    type Error = Node<'pr>;")?;
+    writeln!(file)?;
+    writeln!(file, "    fn try_from(node: Node<'pr>) -> Result<Self, Self::Error> {{")?;
+    writeln!(file, "        match node {{")?;
+    writeln!(file, "            Node::{} {{ parser, pointer, marker }} => Ok(Self {{ parser, pointer, marker }}),", node.name)?;
+    writeln!(file, "            _ => Err(node),")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+    writeln!(file, "impl PartialEq for {}<'_> {{", node.name)?;
+    writeln!(file, "    fn eq(&self, other: &Self) -> bool {{")?;
+    writeln!(file, "        self.pointer == other.pointer")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+    writeln!(file, "impl Eq for {}<'_> {{}}", node.name)?;
+    writeln!(file)?;
+    writeln!(file, "impl Hash for {}<'_> {{", node.name)?;
+    writeln!(file, "    fn hash<H: Hasher>(&self, state: &mut H) {{")?;
+    writeln!(file, "        self.pointer.hash(state);")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+    writeln!(file, "// Nodes only read from the finished tree, see the note on thread safety in `lib.rs`.")?;
+    writeln!(file, "unsafe impl Send for {}<'_> {{}}", node.name)?;
+    writeln!(file, "unsafe impl Sync for {}<'_> {{}}", node.name)?;
+    writeln!(file)?;
     writeln!(file, "impl<'pr> {}<'pr> {{", node.name)?;
     writeln!(file, "    /// Converts this node to a generic node.")?;
     writeln!(file, "    #[must_use]")?;
@@ -243,6 +314,12 @@ fn write_node(file: &mut File, flags: &[Flags], node: &Node) -> Result<(), Box<d
     writeln!(file, "        Location::new(self.parser, unsafe {{ &(*pointer) }})")?;
     writeln!(file, "    }}")?;
     writeln!(file)?;
+    writeln!(file, "    /// Returns the identifier of this node.")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub fn id(&self) -> NodeId {{")?;
+    writeln!(file, "        NodeId::new(unsafe {{ (*self.pointer).base.node_id }})")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
     writeln!(file, "    /// Returns the flags of this node.")?;
     writeln!(file, "    #[must_use]")?;
     writeln!(file, "    pub fn flags(&self) -> pm_node_flags_t {{")?;
@@ -316,7 +393,7 @@ fn write_node(file: &mut File, flags: &[Flags], node: &Node) -> Result<(), Box<d
                 writeln!(file, "    }}")?;
             },
             NodeFieldType::String => {
-                writeln!(file, "    pub fn {}(&self) -> &[u8] {{", field.name)?;
+                writeln!(file, "    pub fn {}(&self) -> &'pr [u8] {{", field.name)?;
                 writeln!(file, "        unsafe {{")?;
                 writeln!(file, "            let source = (*self.pointer).{}.source;", field.name)?;
                 writeln!(file, "            if source.is_null() {{")?;
@@ -528,6 +605,298 @@ fn write_visit(file: &mut File, config: &Config) -> Result<(), Box<dyn std::erro
     Ok(())
 }
 
+/// Write the short-circuiting visit trait to the file.
+fn write_try_visit(file: &mut File, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(file, "/// Whether a [`TryVisit`] visitor should skip the children of the node that it")?;
+    writeln!(file, "/// just visited.")?;
+    writeln!(file, "#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]")?;
+    writeln!(file, "pub enum Skip {{")?;
+    writeln!(file, "    /// Descend into the children of the node.")?;
+    writeln!(file, "    #[default]")?;
+    writeln!(file, "    Nothing,")?;
+    writeln!(file, "    /// Move on to the next sibling without visiting the children of the node.")?;
+    writeln!(file, "    Children,")?;
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+    writeln!(file, "/// A trait for visiting the AST that can stop the traversal early.")?;
+    writeln!(file, "///")?;
+    writeln!(file, "/// Each `visit_*_node` method is called before the children of the node are")?;
+    writeln!(file, "/// visited. Returning `ControlFlow::Break` stops the traversal entirely and")?;
+    writeln!(file, "/// hands the value back to the caller of [`TryVisit::visit`], while returning")?;
+    writeln!(file, "/// `ControlFlow::Continue(Skip::Children)` skips the children of that node.")?;
+    writeln!(file, "pub trait TryVisit<'pr> {{")?;
+    writeln!(file, "    /// The value that the traversal stops with.")?;
+    writeln!(file, "    type Break;")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Visits a node and its children, stopping as soon as a visit method")?;
+    writeln!(file, "    /// breaks.")?;
+    writeln!(file, "    fn visit(&mut self, node: &Node<'pr>) -> ControlFlow<Self::Break> {{")?;
+    writeln!(file, "        match node {{")?;
+
+    for node in &config.nodes {
+        writeln!(file, "            Node::{} {{ parser, pointer, marker }} => {{", node.name)?;
+        writeln!(file, "                let concrete = {} {{ parser: *parser, pointer: *pointer, marker: *marker }};", node.name)?;
+        writeln!(file, "                match self.visit{}(&concrete)? {{", struct_name(&node.name))?;
+        writeln!(file, "                    Skip::Nothing => try_visit{}(self, &concrete),", struct_name(&node.name))?;
+        writeln!(file, "                    Skip::Children => ControlFlow::Continue(()),")?;
+        writeln!(file, "                }}")?;
+        writeln!(file, "            }}")?;
+    }
+
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+
+    for node in &config.nodes {
+        writeln!(file)?;
+        writeln!(file, "    /// Visits a `{}` node before its children.", node.name)?;
+        writeln!(file, "    fn visit{}(&mut self, _node: &{}<'pr>) -> ControlFlow<Self::Break, Skip> {{", struct_name(&node.name), node.name)?;
+        writeln!(file, "        ControlFlow::Continue(Skip::Nothing)")?;
+        writeln!(file, "    }}")?;
+    }
+    writeln!(file, "}}")?;
+
+    for node in &config.nodes {
+        writeln!(file)?;
+        writeln!(file, "/// Visits the children of a `{}` node with a [`TryVisit`] visitor.", node.name)?;
+
+        let children = node.fields.iter().any(|f| matches!(f.field_type, NodeFieldType::Node | NodeFieldType::OptionalNode | NodeFieldType::NodeList));
+
+        if children {
+            writeln!(file, "pub fn try_visit{}<'pr, V>(visitor: &mut V, node: &{}<'pr>) -> ControlFlow<V::Break>", struct_name(&node.name), node.name)?;
+            writeln!(file, "where")?;
+            writeln!(file, "    V: TryVisit<'pr> + ?Sized,")?;
+            writeln!(file, "{{")?;
+
+            for field in &node.fields {
+                match field.field_type {
+                    NodeFieldType::Node => {
+                        if matches!(&field.kind, Some(NodeFieldKind::Concrete(raw_kind)) if !kind_to_type(raw_kind).is_empty()) {
+                            writeln!(file, "    visitor.visit(&node.{}().as_node())?;", field.name)?;
+                        } else {
+                            writeln!(file, "    visitor.visit(&node.{}())?;", field.name)?;
+                        }
+                    },
+                    NodeFieldType::OptionalNode => {
+                        writeln!(file, "    if let Some(node) = node.{}() {{", field.name)?;
+                        if matches!(&field.kind, Some(NodeFieldKind::Concrete(raw_kind)) if !kind_to_type(raw_kind).is_empty()) {
+                            writeln!(file, "        visitor.visit(&node.as_node())?;")?;
+                        } else {
+                            writeln!(file, "        visitor.visit(&node)?;")?;
+                        }
+                        writeln!(file, "    }}")?;
+                    },
+                    NodeFieldType::NodeList => {
+                        writeln!(file, "    for node in &node.{}() {{", field.name)?;
+                        writeln!(file, "        visitor.visit(&node)?;")?;
+                        writeln!(file, "    }}")?;
+                    },
+                    _ => {},
+                }
+            }
+
+            writeln!(file, "    ControlFlow::Continue(())")?;
+            writeln!(file, "}}")?;
+        } else {
+            writeln!(file, "pub const fn try_visit{}<'pr, V>(_visitor: &mut V, _node: &{}<'pr>) -> ControlFlow<V::Break>", struct_name(&node.name), node.name)?;
+            writeln!(file, "where")?;
+            writeln!(file, "    V: TryVisit<'pr> + ?Sized,")?;
+            writeln!(file, "{{")?;
+            writeln!(file, "    ControlFlow::Continue(())")?;
+            writeln!(file, "}}")?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Write the token kind enum to the file.
+fn write_tokens(file: &mut File, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(file, "/// An enum representing the different kinds of tokens that can be lexed.")?;
+    writeln!(file, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]")?;
+    writeln!(file, "pub enum TokenKind {{")?;
+
+    for token in &config.tokens {
+        if let Some(comment) = &token.comment {
+            writeln!(file, "    /// {}", escape_markdown(comment))?;
+        } else {
+            writeln!(file, "    /// The `{}` token", token.name)?;
+        }
+        writeln!(file, "    {},", variant_name(&token.name))?;
+    }
+
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+
+    writeln!(file, "impl TokenKind {{")?;
+    writeln!(file, "    /// Creates a new token kind from the given token type, or returns `None`")?;
+    writeln!(file, "    /// if the token type is unknown.")?;
+    writeln!(file, "    pub(crate) const fn new(token_type: pm_token_type_t) -> Option<Self> {{")?;
+    writeln!(file, "        match token_type {{")?;
+
+    for token in &config.tokens {
+        writeln!(file, "            pm_token_type::PM_TOKEN_{} => Some(Self::{}),", token.name, variant_name(&token.name))?;
+    }
+
+    writeln!(file, "            _ => None,")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns the name of this token kind, as it is written in prism's")?;
+    writeln!(file, "    /// configuration (e.g. `KEYWORD_DEF`).")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub const fn name(self) -> &'static str {{")?;
+    writeln!(file, "        match self {{")?;
+
+    for token in &config.tokens {
+        writeln!(file, "            Self::{} => \"{}\",", variant_name(&token.name), token.name)?;
+    }
+
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+/// Write the node kind enum to the file.
+fn write_node_kinds(file: &mut File, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    writeln!(file, "/// An enum representing the different kinds of nodes, without any of their")?;
+    writeln!(file, "/// data. Kinds are ordered the same way as prism's `pm_node_type` values.")?;
+    writeln!(file, "#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]")?;
+    writeln!(file, "pub enum NodeKind {{")?;
+
+    // The node types in prism are numbered in order of their names.
+    let mut nodes: Vec<&Node> = config.nodes.iter().collect();
+    nodes.sort_by(|left, right| left.name.cmp(&right.name));
+
+    for node in &nodes {
+        writeln!(file, "    /// The kind of a `{}`", node.name)?;
+        writeln!(file, "    {},", node.name)?;
+    }
+
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+
+    writeln!(file, "impl NodeKind {{")?;
+    writeln!(file, "    /// Every node kind, in order.")?;
+    writeln!(file, "    pub const ALL: [Self; {}] = [", nodes.len())?;
+    for node in &nodes {
+        writeln!(file, "        Self::{},", node.name)?;
+    }
+    writeln!(file, "    ];")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns the name of this node kind as prism reports it through")?;
+    writeln!(file, "    /// `pm_node_type` (e.g. `PM_CALL_NODE`).")?;
+    writeln!(file, "    ///")?;
+    writeln!(file, "    /// # Panics")?;
+    writeln!(file, "    ///")?;
+    writeln!(file, "    /// Panics if prism reports a name that is not valid UTF-8.")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub fn name(self) -> &'static str {{")?;
+    writeln!(file, "        let name = unsafe {{ std::ffi::CStr::from_ptr(pm_node_type(self.node_type())) }};")?;
+    writeln!(file, "        name.to_str().expect(\"prism node type names are ASCII.\")")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns the prism node type for this node kind.")?;
+    writeln!(file, "    const fn node_type(self) -> pm_node_type_t {{")?;
+    writeln!(file, "        match self {{")?;
+    for node in &nodes {
+        writeln!(file, "            Self::{} => {},", node.name, type_name(&node.name))?;
+    }
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+
+    writeln!(file, "impl Node<'_> {{")?;
+    writeln!(file, "    /// Returns the kind of this node.")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub const fn kind(&self) -> NodeKind {{")?;
+    writeln!(file, "        match *self {{")?;
+    for node in &nodes {
+        writeln!(file, "            Self::{} {{ .. }} => NodeKind::{},", node.name, node.name)?;
+    }
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
+/// Write the diagnostic kind enum to the file.
+fn write_diagnostic_kinds(file: &mut File, config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    // Some diagnostics (e.g. `INVALID_CHARACTER`) are both errors and warnings.
+    // Prism reports them by name, so they share a single variant.
+    let mut names: Vec<&String> = Vec::with_capacity(config.errors.len() + config.warnings.len());
+    for name in config.errors.iter().chain(&config.warnings) {
+        if !names.contains(&name) {
+            names.push(name);
+        }
+    }
+
+    writeln!(file, "/// An enum representing the different kinds of diagnostics that can be")?;
+    writeln!(file, "/// reported by the parser.")?;
+    writeln!(file, "#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]")?;
+    writeln!(file, "pub enum DiagnosticKind {{")?;
+
+    for name in &names {
+        writeln!(file, "    /// The `{}` diagnostic", name.to_lowercase())?;
+        writeln!(file, "    {},", variant_name(name))?;
+    }
+
+    writeln!(file, "}}")?;
+    writeln!(file)?;
+
+    writeln!(file, "impl DiagnosticKind {{")?;
+    writeln!(file, "    /// Returns the diagnostic kind with the given name, as it is returned")?;
+    writeln!(file, "    /// by `pm_diagnostic_type` (e.g. `ambiguous_first_argument_plus`).")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub fn from_name(name: &str) -> Option<Self> {{")?;
+    writeln!(file, "        match name {{")?;
+
+    for name in &names {
+        writeln!(file, "            \"{}\" => Some(Self::{}),", name.to_lowercase(), variant_name(name))?;
+    }
+
+    writeln!(file, "            _ => None")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns the name of this diagnostic kind (e.g.")?;
+    writeln!(file, "    /// `ambiguous_first_argument_plus`).")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub const fn name(self) -> &'static str {{")?;
+    writeln!(file, "        match self {{")?;
+
+    for name in &names {
+        writeln!(file, "            Self::{} => \"{}\",", variant_name(name), name.to_lowercase())?;
+    }
+
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    // Diagnostic IDs number the errors and then the warnings in the order they
+    // appear in the config, both in `pm_diagnostic_id` and when serialized.
+    writeln!(file, "    /// Every diagnostic kind, indexed by its ID.")?;
+    writeln!(file, "    const BY_ID: &'static [Self] = &[")?;
+
+    for name in config.errors.iter().chain(&config.warnings) {
+        writeln!(file, "        Self::{},", variant_name(name))?;
+    }
+
+    writeln!(file, "    ];")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns the diagnostic kind with the given ID, or `None` if the ID is")?;
+    writeln!(file, "    /// unknown.")?;
+    writeln!(file, "    pub(crate) fn from_id(id: u32) -> Option<Self> {{")?;
+    writeln!(file, "        Self::BY_ID.get(id as usize).copied()")?;
+    writeln!(file, "    }}")?;
+    writeln!(file, "}}")?;
+
+    Ok(())
+}
+
 /// Write the bindings to the `$OUT_DIR/bindings.rs` file. We'll pull these into
 /// the actual library in `src/lib.rs`.
 fn write_bindings(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
@@ -538,11 +907,13 @@ fn write_bindings(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
     write!(
         file,
         r"
+use std::hash::{{Hash, Hasher}};
 use std::marker::PhantomData;
+use std::ops::ControlFlow;
 
 #[allow(clippy::wildcard_imports)]
 use ruby_prism_sys::*;
-use crate::{{ConstantId, ConstantList, Integer, Location, NodeList}};
+use crate::{{ConstantId, ConstantList, FieldValue, Integer, Location, NodeId, NodeList}};
 "
     )?;
 
@@ -614,6 +985,278 @@ impl<'pr> Node<'pr> {{
     writeln!(file, "    }}")?;
     writeln!(file)?;
 
+    writeln!(file, "    /// Returns the parser that this node belongs to.")?;
+    writeln!(file, "    pub(crate) const fn parser(&self) -> *const pm_parser_t {{")?;
+    writeln!(file, "        match *self {{")?;
+    for (index, node) in config.nodes.iter().enumerate() {
+        let separator = if index == 0 { "" } else { "| " };
+        writeln!(file, "            {separator}Self::{} {{ parser, .. }}", node.name)?;
+    }
+    writeln!(file, "            => parser,")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    writeln!(file, "    /// Calls the given closure with the name of each child node field and the")?;
+    writeln!(file, "    /// node in it, in the order of the fields. Missing optional nodes are passed")?;
+    writeln!(file, "    /// as `None`, and node lists pass each of their nodes under the same name.")?;
+    writeln!(file, "    pub(crate) fn each_child_with_name<F: FnMut(&'static str, Option<Node<'pr>>)>(&self, mut f: F) {{")?;
+    writeln!(file, "        match *self {{")?;
+    let mut leaves = Vec::new();
+    for node in &config.nodes {
+        let children: Vec<&NodeField> = node.fields.iter().filter(|f| matches!(f.field_type, NodeFieldType::Node | NodeFieldType::OptionalNode | NodeFieldType::NodeList)).collect();
+        if children.is_empty() {
+            leaves.push(node);
+            continue;
+        }
+
+        writeln!(file, "            Self::{} {{ parser, pointer, marker }} => {{", node.name)?;
+        writeln!(file, "                let node = {} {{ parser, pointer, marker }};", node.name)?;
+        for field in children {
+            let concrete = matches!(&field.kind, Some(NodeFieldKind::Concrete(raw_kind)) if !kind_to_type(raw_kind).is_empty());
+            match field.field_type {
+                NodeFieldType::Node => {
+                    let child = if concrete { format!("node.{}().as_node()", field.name) } else { format!("node.{}()", field.name) };
+                    writeln!(file, "                f(\"{}\", Some({child}));", field.name)?;
+                },
+                NodeFieldType::OptionalNode => {
+                    let child = if concrete { format!("node.{}().map(|child| child.as_node())", field.name) } else { format!("node.{}()", field.name) };
+                    writeln!(file, "                f(\"{}\", {child});", field.name)?;
+                },
+                _ => {
+                    writeln!(file, "                for child in &node.{}() {{", field.name)?;
+                    writeln!(file, "                    f(\"{}\", Some(child));", field.name)?;
+                    writeln!(file, "                }}")?;
+                },
+            }
+        }
+        writeln!(file, "            }},")?;
+    }
+    for (index, node) in leaves.iter().enumerate() {
+        let separator = if index == 0 { "" } else { "| " };
+        writeln!(file, "            {separator}Self::{} {{ .. }}", node.name)?;
+    }
+    writeln!(file, "            => {{}},")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Calls the given closure with each of the direct children of this node,")?;
+    writeln!(file, "    /// in the order of the fields, skipping missing optional nodes.")?;
+    writeln!(file, "    pub(crate) fn each_child<F: FnMut(Node<'pr>)>(&self, mut f: F) {{")?;
+    writeln!(file, "        self.each_child_with_name(|_, child| {{")?;
+    writeln!(file, "            if let Some(child) = child {{")?;
+    writeln!(file, "                f(child);")?;
+    writeln!(file, "            }}")?;
+    writeln!(file, "        }});")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns an iterator over the direct children of this node in the order")?;
+    writeln!(file, "    /// of its fields, excluding missing optional nodes. This matches")?;
+    writeln!(file, "    /// `compact_child_nodes` in the Ruby API.")?;
+    writeln!(file, "    pub fn children(&self) -> impl Iterator<Item = Node<'pr>> {{")?;
+    writeln!(file, "        let mut children = Vec::new();")?;
+    writeln!(file, "        self.each_child(|child| children.push(child));")?;
+    writeln!(file, "        children.into_iter()")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+    writeln!(file, "    /// Returns an iterator over the direct children of this node paired with")?;
+    writeln!(file, "    /// the names of the fields that hold them. Missing optional nodes are")?;
+    writeln!(file, "    /// yielded as `None`, and each node in a list is yielded under the name of")?;
+    writeln!(file, "    /// the list. This matches the order of `child_nodes` in the Ruby API.")?;
+    writeln!(file, "    pub fn child_nodes_with_names(&self) -> impl Iterator<Item = (&'static str, Option<Node<'pr>>)> {{")?;
+    writeln!(file, "        let mut children = Vec::new();")?;
+    writeln!(file, "        self.each_child_with_name(|name, child| children.push((name, child)));")?;
+    writeln!(file, "        children.into_iter()")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    writeln!(file, "    /// Returns the name and value of every field on this node in the order of")?;
+    writeln!(file, "    /// the prism config, starting with the flags for nodes that have them.")?;
+    writeln!(file, "    /// This mirrors `Prism::Reflection.fields_for` in the Ruby API.")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub fn fields(&self) -> Vec<(&'static str, FieldValue<'pr>)> {{")?;
+    writeln!(file, "        match *self {{")?;
+    let mut empty = Vec::new();
+    for node in &config.nodes {
+        if node.flags.is_none() && node.fields.is_empty() {
+            empty.push(node);
+            continue;
+        }
+
+        writeln!(file, "            Self::{} {{ parser, pointer, marker }} => {{", node.name)?;
+        writeln!(file, "                let node = {} {{ parser, pointer, marker }};", node.name)?;
+        writeln!(file, "                vec![")?;
+        if node.flags.is_some() {
+            writeln!(file, "                    (\"flags\", FieldValue::Flags(node.flags())),")?;
+        }
+        for field in &node.fields {
+            let concrete = matches!(&field.kind, Some(NodeFieldKind::Concrete(raw_kind)) if !kind_to_type(raw_kind).is_empty());
+            let value = match field.field_type {
+                NodeFieldType::Node if concrete => format!("Node(node.{}().as_node())", field.name),
+                NodeFieldType::Node => format!("Node(node.{}())", field.name),
+                NodeFieldType::OptionalNode if concrete => format!("OptionalNode(node.{}().map(|node| node.as_node()))", field.name),
+                NodeFieldType::OptionalNode => format!("OptionalNode(node.{}())", field.name),
+                NodeFieldType::NodeList => format!("NodeList(node.{}())", field.name),
+                NodeFieldType::String => format!("String(node.{}())", field.name),
+                NodeFieldType::Constant => format!("Constant(node.{}())", field.name),
+                NodeFieldType::OptionalConstant => format!("OptionalConstant(node.{}())", field.name),
+                NodeFieldType::ConstantList => format!("ConstantList(node.{}())", field.name),
+                NodeFieldType::Location => format!("Location(node.{}())", field.name),
+                NodeFieldType::OptionalLocation => format!("OptionalLocation(node.{}())", field.name),
+                NodeFieldType::UInt8 => format!("UInt8(node.{}())", field.name),
+                NodeFieldType::UInt32 => format!("UInt32(node.{}())", field.name),
+                NodeFieldType::Integer => format!("Integer(node.{}())", field.name),
+                NodeFieldType::Double => format!("Double(node.{}())", field.name),
+            };
+            writeln!(file, "                    (\"{}\", FieldValue::{value}),", field.name)?;
+        }
+        writeln!(file, "                ]")?;
+        writeln!(file, "            }},")?;
+    }
+    for (index, node) in empty.iter().enumerate() {
+        let separator = if index == 0 { "" } else { "| " };
+        writeln!(file, "            {separator}Self::{} {{ .. }}", node.name)?;
+    }
+    writeln!(file, "            => Vec::new(),")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    writeln!(file, "    /// Returns true if this node has the same structure as the given node, which")?;
+    writeln!(file, "    /// may come from a different parse result.")?;
+    writeln!(file, "    ///")?;
+    writeln!(file, "    /// Locations are ignored, apart from whether optional locations are present.")?;
+    writeln!(file, "    /// Every other field is compared by value, recursing into child nodes. This")?;
+    writeln!(file, "    /// mirrors `Prism::Node#===` in the Ruby API.")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub fn structurally_eq(&self, other: &Node<'_>) -> bool {{")?;
+    writeln!(file, "        match (self, other) {{")?;
+    let mut trivial = Vec::new();
+    for node in &config.nodes {
+        let mut comparisons = Vec::new();
+        if node.flags.is_some() {
+            comparisons.push("left.flags() == right.flags()".to_owned());
+        }
+        for field in &node.fields {
+            let concrete = matches!(&field.kind, Some(NodeFieldKind::Concrete(raw_kind)) if !kind_to_type(raw_kind).is_empty());
+            let name = &field.name;
+            comparisons.push(match field.field_type {
+                NodeFieldType::Node if concrete => format!("left.{name}().as_node().structurally_eq(&right.{name}().as_node())"),
+                NodeFieldType::Node => format!("left.{name}().structurally_eq(&right.{name}())"),
+                NodeFieldType::OptionalNode if concrete => format!("match (left.{name}(), right.{name}()) {{ (Some(left_child), Some(right_child)) => left_child.as_node().structurally_eq(&right_child.as_node()), (None, None) => true, _ => false }}"),
+                NodeFieldType::OptionalNode => format!("match (left.{name}(), right.{name}()) {{ (Some(left_child), Some(right_child)) => left_child.structurally_eq(&right_child), (None, None) => true, _ => false }}"),
+                NodeFieldType::NodeList | NodeFieldType::ConstantList => format!("left.{name}().structurally_eq(&right.{name}())"),
+                NodeFieldType::String | NodeFieldType::UInt8 | NodeFieldType::UInt32 => format!("left.{name}() == right.{name}()"),
+                NodeFieldType::Constant => format!("left.{name}().as_slice() == right.{name}().as_slice()"),
+                NodeFieldType::OptionalConstant => format!("left.{name}().map(|constant| constant.as_slice()) == right.{name}().map(|constant| constant.as_slice())"),
+                NodeFieldType::Location => continue,
+                NodeFieldType::OptionalLocation => format!("left.{name}().is_some() == right.{name}().is_some()"),
+                NodeFieldType::Integer => format!("left.{name}().to_u32_digits() == right.{name}().to_u32_digits()"),
+                NodeFieldType::Double => format!("left.{name}().to_bits() == right.{name}().to_bits()"),
+            });
+        }
+
+        if comparisons.is_empty() {
+            trivial.push(node);
+            continue;
+        }
+
+        // A match can't start a chain of comparisons, so parenthesize it.
+        if comparisons.len() > 1 {
+            for comparison in &mut comparisons {
+                if comparison.starts_with("match") {
+                    *comparison = format!("({comparison})");
+                }
+            }
+        }
+
+        writeln!(file, "            (&Self::{} {{ parser, pointer, .. }}, &Node::{} {{ parser: other_parser, pointer: other_pointer, .. }}) => {{", node.name, node.name)?;
+        writeln!(file, "                let left = {} {{ parser, pointer, marker: PhantomData }};", node.name)?;
+        writeln!(file, "                let right = {} {{ parser: other_parser, pointer: other_pointer, marker: PhantomData }};", node.name)?;
+        writeln!(file, "                {}", comparisons.join("\n                    && "))?;
+        writeln!(file, "            }},")?;
+    }
+    for (index, node) in trivial.iter().enumerate() {
+        let separator = if index == 0 { "" } else { "| " };
+        writeln!(file, "            {separator}(Self::{} {{ .. }}, Node::{} {{ .. }})", node.name, node.name)?;
+    }
+    writeln!(file, "            => true,")?;
+    writeln!(file, "            _ => false,")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    writeln!(file, "    /// Returns a hash of the structure of this node, consistent with")?;
+    writeln!(file, "    /// [`Node::structurally_eq`]. Nodes that are structurally equal have the same")?;
+    writeln!(file, "    /// hash, wherever they appear in the source.")?;
+    writeln!(file, "    #[must_use]")?;
+    writeln!(file, "    pub fn structural_hash(&self) -> u64 {{")?;
+    writeln!(file, "        let mut hasher = std::collections::hash_map::DefaultHasher::new();")?;
+    writeln!(file, "        self.hash_structure(&mut hasher);")?;
+    writeln!(file, "        hasher.finish()")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    writeln!(file, "    /// Feeds the structure of this node into the given hasher.")?;
+    writeln!(file, "    pub(crate) fn hash_structure<H: Hasher>(&self, state: &mut H) {{")?;
+    writeln!(file, "        self.kind().hash(state);")?;
+    writeln!(file)?;
+    writeln!(file, "        match *self {{")?;
+    let mut trivial = Vec::new();
+    for node in &config.nodes {
+        let mut statements = Vec::new();
+        if node.flags.is_some() {
+            statements.push("node.flags().hash(state);".to_owned());
+        }
+        for field in &node.fields {
+            let concrete = matches!(&field.kind, Some(NodeFieldKind::Concrete(raw_kind)) if !kind_to_type(raw_kind).is_empty());
+            let name = &field.name;
+            statements.push(match field.field_type {
+                NodeFieldType::Node if concrete => format!("node.{name}().as_node().hash_structure(state);"),
+                NodeFieldType::Node | NodeFieldType::NodeList | NodeFieldType::ConstantList => format!("node.{name}().hash_structure(state);"),
+                NodeFieldType::OptionalNode if concrete => format!("match node.{name}() {{ Some(child) => {{ true.hash(state); child.as_node().hash_structure(state); }}, None => false.hash(state) }}"),
+                NodeFieldType::OptionalNode => format!("match node.{name}() {{ Some(child) => {{ true.hash(state); child.hash_structure(state); }}, None => false.hash(state) }}"),
+                NodeFieldType::String | NodeFieldType::UInt8 | NodeFieldType::UInt32 => format!("node.{name}().hash(state);"),
+                NodeFieldType::Constant => format!("node.{name}().as_slice().hash(state);"),
+                NodeFieldType::OptionalConstant => format!("node.{name}().map(|constant| constant.as_slice()).hash(state);"),
+                NodeFieldType::Location => continue,
+                NodeFieldType::OptionalLocation => format!("node.{name}().is_some().hash(state);"),
+                NodeFieldType::Integer => format!("node.{name}().to_u32_digits().hash(state);"),
+                NodeFieldType::Double => format!("node.{name}().to_bits().hash(state);"),
+            });
+        }
+
+        if statements.is_empty() {
+            trivial.push(node);
+            continue;
+        }
+
+        writeln!(file, "            Self::{} {{ parser, pointer, marker }} => {{", node.name)?;
+        writeln!(file, "                let node = {} {{ parser, pointer, marker }};", node.name)?;
+        for statement in statements {
+            writeln!(file, "                {statement}")?;
+        }
+        writeln!(file, "            }},")?;
+    }
+    for (index, node) in trivial.iter().enumerate() {
+        let separator = if index == 0 { "" } else { "| " };
+        writeln!(file, "            {separator}Self::{} {{ .. }}", node.name)?;
+    }
+    writeln!(file, "            => {{}}")?;
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
+    writeln!(file, "    /// Returns a pointer to the underlying C node.")?;
+    writeln!(file, "    pub(crate) const fn as_ptr(&self) -> *mut pm_node_t {{")?;
+    writeln!(file, "        match *self {{")?;
+    for node in &config.nodes {
+        writeln!(file, "            Self::{} {{ pointer, .. }} => pointer.cast::<pm_node_t>(),", node.name)?;
+    }
+    writeln!(file, "        }}")?;
+    writeln!(file, "    }}")?;
+    writeln!(file)?;
+
     for node in &config.nodes {
         writeln!(file, "    /// Returns the node as a `{}`.", node.name)?;
         writeln!(file, "    #[must_use]")?;
@@ -647,6 +1290,68 @@ impl<'pr> Node<'pr> {{
     }
 
     write_visit(&mut file, config)?;
+    writeln!(file)?;
+
+    write_try_visit(&mut file, config)?;
+    writeln!(file)?;
+
+    write_tokens(&mut file, config)?;
+    writeln!(file)?;
+
+    write_diagnostic_kinds(&mut file, config)?;
+    writeln!(file)?;
+
+    write_node_kinds(&mut file, config)?;
+
+    Ok(())
+}
+
+/// Write the tables that drive the deserializer to the `$OUT_DIR/deserialize.rs`
+/// file. We'll pull these into the `deserialize` module in `src/deserialize.rs`.
+fn write_deserialize(config: &Config) -> Result<(), Box<dyn std::error::Error>> {
+    let out_dir = std::env::var_os("OUT_DIR").unwrap();
+    let dest_path = Path::new(&out_dir).join("deserialize.rs");
+    let mut file = std::fs::File::create(&dest_path).expect("Unable to create file");
+
+    // The node types in the serialization format are numbered by their
+    // position in the list of nodes sorted by name, starting from 1.
+    let mut nodes: Vec<&Node> = config.nodes.iter().collect();
+    nodes.sort_by(|left, right| left.name.cmp(&right.name));
+
+    writeln!(file, "/// The schema of every node, indexed by its serialized type minus one.")?;
+    writeln!(file, "const NODE_SCHEMAS: &[NodeSchema] = &[")?;
+
+    for node in nodes {
+        writeln!(file, "    NodeSchema {{")?;
+        writeln!(file, "        name: \"{}\",", node.name)?;
+        writeln!(file, "        has_length: {},", node.name == "DefNode")?;
+        writeln!(file, "        fields: &[")?;
+
+        for field in &node.fields {
+            let field_type = match field.field_type {
+                NodeFieldType::Node => "Node",
+                NodeFieldType::OptionalNode => "OptionalNode",
+                NodeFieldType::NodeList => "NodeList",
+                NodeFieldType::String => "String",
+                NodeFieldType::Constant => "Constant",
+                NodeFieldType::OptionalConstant => "OptionalConstant",
+                NodeFieldType::ConstantList => "ConstantList",
+                NodeFieldType::Location => "Location",
+                NodeFieldType::OptionalLocation => "OptionalLocation",
+                NodeFieldType::UInt8 => "UInt8",
+                NodeFieldType::UInt32 => "UInt32",
+                NodeFieldType::Integer => "Integer",
+                NodeFieldType::Double => "Double",
+            };
+            writeln!(file, "            (\"{}\", FieldType::{}),", field.name, field_type)?;
+        }
+
+        writeln!(file, "        ],")?;
+        writeln!(file, "    }},")?;
+    }
+
+    writeln!(file, "];")?;
+    writeln!(file)?;
 
     Ok(())
 }
@@ -0,0 +1,738 @@
+//! Deserialization of parse results from prism's binary serialization format.
+//!
+//! The format is described in `docs/serialization.md`. The syntax tree that is
+//! read back is owned, so it can be cached and loaded in another process
+//! without reparsing. Locations are byte offsets into the original source,
+//! which is not part of the serialized format.
+
+use std::fmt;
+
+use ruby_prism_sys::{PRISM_VERSION_MAJOR, PRISM_VERSION_MINOR, PRISM_VERSION_PATCH};
+
+use crate::{CommentType, DiagnosticKind, ErrorLevel, WarningLevel};
+
+/// The type of a field on a node, which determines how it is serialized.
+#[derive(Debug, Clone, Copy)]
+enum FieldType {
+    Node,
+    OptionalNode,
+    NodeList,
+    String,
+    Constant,
+    OptionalConstant,
+    ConstantList,
+    Location,
+    OptionalLocation,
+    UInt8,
+    UInt32,
+    Integer,
+    Double,
+}
+
+/// The name and fields of a node type.
+struct NodeSchema {
+    name: &'static str,
+    has_length: bool,
+    fields: &'static [(&'static str, FieldType)],
+}
+
+/// The major, minor and patch version of prism that serialized strings must
+/// have been written by. This is the version of the C library that the crate
+/// links against, which writes it into the header in `pm_serialize`.
+#[allow(clippy::cast_possible_truncation)]
+const VERSION: (u8, u8, u8) = (PRISM_VERSION_MAJOR as u8, PRISM_VERSION_MINOR as u8, PRISM_VERSION_PATCH as u8);
+
+// In `build.rs`, we generate the node schemas and the diagnostic kinds in the
+// order that the serialization format uses to identify them.
+include!(concat!(env!("OUT_DIR"), "/deserialize.rs"));
+
+/// Errors that can occur when deserializing a parse result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DeserializeError {
+    /// The input does not start with the `PRISM` header.
+    InvalidHeader,
+    /// The input was serialized by a different version of prism.
+    VersionMismatch {
+        /// The major version of prism that serialized the input.
+        major: u8,
+        /// The minor version of prism that serialized the input.
+        minor: u8,
+        /// The patch version of prism that serialized the input.
+        patch: u8,
+    },
+    /// The input was serialized without location fields.
+    MissingLocations,
+    /// The input ended before the parse result was fully read.
+    UnexpectedEof,
+    /// The input contains a value that is not valid in the format.
+    Invalid(&'static str),
+}
+
+impl fmt::Display for DeserializeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidHeader => {
+                write!(f, "Invalid serialization header")
+            },
+            Self::VersionMismatch { major, minor, patch } => {
+                let (expected_major, expected_minor, expected_patch) = VERSION;
+                write!(f, "Serialized by prism {major}.{minor}.{patch}, expected {expected_major}.{expected_minor}.{expected_patch}")
+            },
+            Self::MissingLocations => {
+                write!(f, "Invalid serialization (location fields must be included but are not)")
+            },
+            Self::UnexpectedEof => {
+                write!(f, "Unexpected end of serialized input")
+            },
+            Self::Invalid(message) => {
+                write!(f, "Invalid serialization ({message})")
+            },
+        }
+    }
+}
+
+impl std::error::Error for DeserializeError {}
+
+/// A range in the source that was serialized, represented as a start offset
+/// and length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SerializedLocation {
+    start: u32,
+    length: u32,
+}
+
+impl SerializedLocation {
+    /// Returns the start offset from the beginning of the parsed source.
+    #[must_use]
+    pub const fn start(&self) -> u32 {
+        self.start
+    }
+
+    /// Returns the length of the location in bytes.
+    #[must_use]
+    pub const fn length(&self) -> u32 {
+        self.length
+    }
+
+    /// Returns the end offset from the beginning of the parsed source.
+    #[must_use]
+    pub const fn end(&self) -> u32 {
+        self.start + self.length
+    }
+}
+
+/// An arbitrarily-sized integer that was serialized.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct SerializedInteger {
+    negative: bool,
+    words: Vec<u32>,
+}
+
+impl SerializedInteger {
+    /// Returns the sign and the words of the integer, from least significant
+    /// to most significant.
+    #[must_use]
+    pub fn to_u32_digits(&self) -> (bool, &[u32]) {
+        (self.negative, &self.words)
+    }
+}
+
+/// The value of a field on a serialized node.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SerializedField {
+    /// A child node.
+    Node(Box<SerializedNode>),
+    /// A child node that may be absent.
+    OptionalNode(Option<Box<SerializedNode>>),
+    /// A list of child nodes.
+    NodeList(Vec<SerializedNode>),
+    /// A string, such as the unescaped content of a string literal.
+    String(Vec<u8>),
+    /// A constant from the constant pool.
+    Constant(Vec<u8>),
+    /// A constant from the constant pool that may be absent.
+    OptionalConstant(Option<Vec<u8>>),
+    /// A list of constants from the constant pool.
+    ConstantList(Vec<Vec<u8>>),
+    /// A location in the source.
+    Location(SerializedLocation),
+    /// A location in the source that may be absent.
+    OptionalLocation(Option<SerializedLocation>),
+    /// An 8-bit unsigned integer.
+    UInt8(u8),
+    /// A 32-bit unsigned integer.
+    UInt32(u32),
+    /// An arbitrarily-sized integer.
+    Integer(SerializedInteger),
+    /// A floating point number.
+    Double(f64),
+}
+
+/// A node that was read back from a serialized parse result.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerializedNode {
+    name: &'static str,
+    node_id: u32,
+    location: SerializedLocation,
+    flags: u32,
+    fields: Vec<(&'static str, SerializedField)>,
+}
+
+impl SerializedNode {
+    /// Returns the name of the type of the node, e.g. `CallNode`.
+    #[must_use]
+    pub const fn name(&self) -> &'static str {
+        self.name
+    }
+
+    /// Returns the unique identifier of the node within its parse result.
+    #[must_use]
+    pub const fn node_id(&self) -> u32 {
+        self.node_id
+    }
+
+    /// Returns the location of the node.
+    #[must_use]
+    pub const fn location(&self) -> SerializedLocation {
+        self.location
+    }
+
+    /// Returns the flags of the node, which includes both the flags common to
+    /// every node and the flags specific to this type of node.
+    #[must_use]
+    pub const fn flags(&self) -> u32 {
+        self.flags
+    }
+
+    /// Returns the names and values of the fields of the node, in the order in
+    /// which they are declared in `config.yml`.
+    #[must_use]
+    pub fn fields(&self) -> &[(&'static str, SerializedField)] {
+        &self.fields
+    }
+
+    /// Returns the value of the field with the given name, if the node has it.
+    #[must_use]
+    pub fn field(&self, name: &str) -> Option<&SerializedField> {
+        self.fields.iter().find(|(field_name, _)| *field_name == name).map(|(_, value)| value)
+    }
+
+    /// Returns the child nodes of the node, in the order in which they are
+    /// declared in `config.yml`.
+    #[must_use]
+    pub fn child_nodes(&self) -> Vec<&Self> {
+        let mut children = Vec::new();
+
+        for (_, value) in &self.fields {
+            match value {
+                SerializedField::Node(node) | SerializedField::OptionalNode(Some(node)) => children.push(node.as_ref()),
+                SerializedField::NodeList(nodes) => children.extend(nodes),
+                _ => {},
+            }
+        }
+
+        children
+    }
+}
+
+/// A comment that was read back from a serialized parse result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializedComment {
+    type_: CommentType,
+    location: SerializedLocation,
+}
+
+impl SerializedComment {
+    /// Returns the type of the comment.
+    #[must_use]
+    pub const fn type_(&self) -> CommentType {
+        self.type_
+    }
+
+    /// Returns the location of the comment.
+    #[must_use]
+    pub const fn location(&self) -> SerializedLocation {
+        self.location
+    }
+}
+
+/// A magic comment that was read back from a serialized parse result.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SerializedMagicComment {
+    key: SerializedLocation,
+    value: SerializedLocation,
+}
+
+impl SerializedMagicComment {
+    /// Returns the location of the comment's key.
+    #[must_use]
+    pub const fn key(&self) -> SerializedLocation {
+        self.key
+    }
+
+    /// Returns the location of the comment's value.
+    #[must_use]
+    pub const fn value(&self) -> SerializedLocation {
+        self.value
+    }
+}
+
+/// An error or warning that was read back from a serialized parse result.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializedDiagnostic {
+    kind: DiagnosticKind,
+    message: String,
+    location: SerializedLocation,
+    error_level: Option<ErrorLevel>,
+    warning_level: Option<WarningLevel>,
+}
+
+impl SerializedDiagnostic {
+    /// Returns the kind of the diagnostic.
+    #[must_use]
+    pub const fn kind(&self) -> DiagnosticKind {
+        self.kind
+    }
+
+    /// Returns the message associated with the diagnostic.
+    #[must_use]
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Returns the location of the diagnostic.
+    #[must_use]
+    pub const fn location(&self) -> SerializedLocation {
+        self.location
+    }
+
+    /// Returns the level of the diagnostic if it is an error.
+    #[must_use]
+    pub const fn error_level(&self) -> Option<ErrorLevel> {
+        self.error_level
+    }
+
+    /// Returns the level of the diagnostic if it is a warning.
+    #[must_use]
+    pub const fn warning_level(&self) -> Option<WarningLevel> {
+        self.warning_level
+    }
+}
+
+/// A parse result that was read back from prism's serialization format.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SerializedParseResult {
+    encoding: String,
+    start_line: i32,
+    line_offsets: Vec<u32>,
+    comments: Vec<SerializedComment>,
+    magic_comments: Vec<SerializedMagicComment>,
+    data_loc: Option<SerializedLocation>,
+    errors: Vec<SerializedDiagnostic>,
+    warnings: Vec<SerializedDiagnostic>,
+    continuable: bool,
+    node: SerializedNode,
+}
+
+impl SerializedParseResult {
+    /// Returns the name of the encoding of the source.
+    #[must_use]
+    pub fn encoding(&self) -> &str {
+        &self.encoding
+    }
+
+    /// Returns the line number that the source starts on.
+    #[must_use]
+    pub const fn start_line(&self) -> i32 {
+        self.start_line
+    }
+
+    /// Returns the offsets of the start of each line in the source.
+    #[must_use]
+    pub fn line_offsets(&self) -> &[u32] {
+        &self.line_offsets
+    }
+
+    /// Returns the comments in the source.
+    #[must_use]
+    pub fn comments(&self) -> &[SerializedComment] {
+        &self.comments
+    }
+
+    /// Returns the magic comments in the source.
+    #[must_use]
+    pub fn magic_comments(&self) -> &[SerializedMagicComment] {
+        &self.magic_comments
+    }
+
+    /// Returns the location of the __END__ marker and the rest of the content
+    /// of the file, if there is one.
+    #[must_use]
+    pub const fn data_loc(&self) -> Option<SerializedLocation> {
+        self.data_loc
+    }
+
+    /// Returns the errors that were found while parsing.
+    #[must_use]
+    pub fn errors(&self) -> &[SerializedDiagnostic] {
+        &self.errors
+    }
+
+    /// Returns the warnings that were found while parsing.
+    #[must_use]
+    pub fn warnings(&self) -> &[SerializedDiagnostic] {
+        &self.warnings
+    }
+
+    /// Returns true if the source is incomplete but could become valid with
+    /// more input.
+    #[must_use]
+    pub const fn continuable(&self) -> bool {
+        self.continuable
+    }
+
+    /// Returns the root node of the syntax tree.
+    #[must_use]
+    pub const fn node(&self) -> &SerializedNode {
+        &self.node
+    }
+}
+
+/// A cursor over the serialized input.
+struct Reader<'a> {
+    input: &'a [u8],
+    offset: usize,
+    constant_pool_offset: usize,
+    constant_pool_size: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Result<u8, DeserializeError> {
+        let byte = *self.input.get(self.offset).ok_or(DeserializeError::UnexpectedEof)?;
+        self.offset += 1;
+        Ok(byte)
+    }
+
+    fn bytes(&mut self, length: usize) -> Result<&'a [u8], DeserializeError> {
+        let end = self.offset.checked_add(length).ok_or(DeserializeError::UnexpectedEof)?;
+        let bytes = self.input.get(self.offset..end).ok_or(DeserializeError::UnexpectedEof)?;
+        self.offset = end;
+        Ok(bytes)
+    }
+
+    /// Reads a variable-length unsigned integer encoded with LEB128.
+    fn varuint(&mut self) -> Result<u32, DeserializeError> {
+        let mut result = 0;
+
+        for shift in (0..35).step_by(7) {
+            let byte = self.byte()?;
+            result |= u32::from(byte & 0x7f) << shift;
+
+            if byte < 0x80 {
+                return Ok(result);
+            }
+        }
+
+        Err(DeserializeError::Invalid("variable-length integer is too long"))
+    }
+
+    /// Reads a variable-length signed integer encoded with `ZigZag` and LEB128.
+    fn varsint(&mut self) -> Result<i32, DeserializeError> {
+        let value = self.varuint()?;
+        i32::try_from(i64::from(value >> 1) ^ -i64::from(value & 1)).map_err(|_| DeserializeError::Invalid("signed integer is out of range"))
+    }
+
+    /// Reads a fixed-width native-endian unsigned integer.
+    fn uint32(&mut self) -> Result<u32, DeserializeError> {
+        let bytes = self.bytes(4)?.try_into().map_err(|_| DeserializeError::UnexpectedEof)?;
+        Ok(u32::from_ne_bytes(bytes))
+    }
+
+    fn double(&mut self) -> Result<f64, DeserializeError> {
+        let bytes = self.bytes(8)?.try_into().map_err(|_| DeserializeError::UnexpectedEof)?;
+        Ok(f64::from_ne_bytes(bytes))
+    }
+
+    fn string(&mut self) -> Result<Vec<u8>, DeserializeError> {
+        let length = self.varuint()?;
+        Ok(self.bytes(length as usize)?.to_vec())
+    }
+
+    fn location(&mut self) -> Result<SerializedLocation, DeserializeError> {
+        Ok(SerializedLocation { start: self.varuint()?, length: self.varuint()? })
+    }
+
+    fn optional_location(&mut self) -> Result<Option<SerializedLocation>, DeserializeError> {
+        if self.byte()? == 0 {
+            Ok(None)
+        } else {
+            self.location().map(Some)
+        }
+    }
+
+    fn integer(&mut self) -> Result<SerializedInteger, DeserializeError> {
+        let negative = self.byte()? != 0;
+        let length = self.varuint()?;
+        let words = (0..length).map(|_| self.varuint()).collect::<Result<_, _>>()?;
+        Ok(SerializedInteger { negative, words })
+    }
+
+    /// Looks up the contents of the constant with the given 1-based ID in the
+    /// constant pool at the end of the input.
+    fn constant(&self, id: u32) -> Result<Vec<u8>, DeserializeError> {
+        let index = (id as usize).checked_sub(1).filter(|&index| index < self.constant_pool_size).ok_or(DeserializeError::Invalid("constant is out of range"))?;
+
+        let mut entry = Reader { offset: self.constant_pool_offset + index * 8, ..*self };
+        let start = entry.uint32()? as usize;
+        let length = entry.uint32()? as usize;
+
+        entry.offset = start;
+        Ok(entry.bytes(length)?.to_vec())
+    }
+
+    fn constant_field(&mut self) -> Result<Vec<u8>, DeserializeError> {
+        let id = self.varuint()?;
+        self.constant(id)
+    }
+
+    fn node(&mut self) -> Result<SerializedNode, DeserializeError> {
+        let type_ = self.byte()?;
+        let schema = usize::from(type_).checked_sub(1).and_then(|index| NODE_SCHEMAS.get(index)).ok_or(DeserializeError::Invalid("unknown node type"))?;
+
+        let node_id = self.varuint()?;
+        let location = self.location()?;
+
+        // Some nodes are prefixed with their serialized length so that readers
+        // can skip over them lazily. We always read them eagerly.
+        if schema.has_length {
+            self.uint32()?;
+        }
+
+        let flags = self.varuint()?;
+        let fields = schema.fields.iter().map(|&(name, field_type)| Ok((name, self.field(field_type)?))).collect::<Result<_, _>>()?;
+
+        Ok(SerializedNode { name: schema.name, node_id, location, flags, fields })
+    }
+
+    fn optional_node(&mut self) -> Result<Option<SerializedNode>, DeserializeError> {
+        if self.input.get(self.offset) == Some(&0) {
+            self.offset += 1;
+            Ok(None)
+        } else {
+            self.node().map(Some)
+        }
+    }
+
+    fn field(&mut self, field_type: FieldType) -> Result<SerializedField, DeserializeError> {
+        Ok(match field_type {
+            FieldType::Node => SerializedField::Node(Box::new(self.node()?)),
+            FieldType::OptionalNode => SerializedField::OptionalNode(self.optional_node()?.map(Box::new)),
+            FieldType::NodeList => {
+                let length = self.varuint()?;
+                SerializedField::NodeList((0..length).map(|_| self.node()).collect::<Result<_, _>>()?)
+            },
+            FieldType::String => SerializedField::String(self.string()?),
+            FieldType::Constant => SerializedField::Constant(self.constant_field()?),
+            FieldType::OptionalConstant => {
+                let id = self.varuint()?;
+                SerializedField::OptionalConstant(if id == 0 { None } else { Some(self.constant(id)?) })
+            },
+            FieldType::ConstantList => {
+                let length = self.varuint()?;
+                SerializedField::ConstantList((0..length).map(|_| self.constant_field()).collect::<Result<_, _>>()?)
+            },
+            FieldType::Location => SerializedField::Location(self.location()?),
+            FieldType::OptionalLocation => SerializedField::OptionalLocation(self.optional_location()?),
+            FieldType::UInt8 => SerializedField::UInt8(self.byte()?),
+            FieldType::UInt32 => SerializedField::UInt32(self.varuint()?),
+            FieldType::Integer => SerializedField::Integer(self.integer()?),
+            FieldType::Double => SerializedField::Double(self.double()?),
+        })
+    }
+
+    fn diagnostic_kind(&mut self) -> Result<DiagnosticKind, DeserializeError> {
+        let id = self.varuint()?;
+        DiagnosticKind::from_id(id).ok_or(DeserializeError::Invalid("unknown diagnostic type"))
+    }
+
+    fn diagnostics(&mut self, errors: bool) -> Result<Vec<SerializedDiagnostic>, DeserializeError> {
+        let length = self.varuint()?;
+
+        (0..length)
+            .map(|_| {
+                let kind = self.diagnostic_kind()?;
+                let message = String::from_utf8_lossy(&self.string()?).into_owned();
+                let location = self.location()?;
+
+                let (error_level, warning_level) = match (errors, self.byte()?) {
+                    (true, 0) => (Some(ErrorLevel::Syntax), None),
+                    (true, 1) => (Some(ErrorLevel::Argument), None),
+                    (true, 2) => (Some(ErrorLevel::Load), None),
+                    (false, 0) => (None, Some(WarningLevel::Default)),
+                    (false, 1) => (None, Some(WarningLevel::Verbose)),
+                    _ => return Err(DeserializeError::Invalid("unknown diagnostic level")),
+                };
+
+                Ok(SerializedDiagnostic { kind, message, location, error_level, warning_level })
+            })
+            .collect()
+    }
+}
+
+/// Deserializes a parse result that was serialized with
+/// [`ParseResult::serialize`](crate::ParseResult::serialize).
+///
+/// # Errors
+///
+/// Returns [`DeserializeError`] if the input was not serialized by this
+/// version of prism, or if it is truncated or otherwise malformed.
+pub fn deserialize(serialized: &[u8]) -> Result<SerializedParseResult, DeserializeError> {
+    if !serialized.starts_with(b"PRISM") {
+        return Err(DeserializeError::InvalidHeader);
+    }
+
+    let mut reader = Reader {
+        input: serialized,
+        offset: 5,
+        constant_pool_offset: 0,
+        constant_pool_size: 0,
+    };
+
+    let (major, minor, patch) = (reader.byte()?, reader.byte()?, reader.byte()?);
+    if (major, minor, patch) != VERSION {
+        return Err(DeserializeError::VersionMismatch { major, minor, patch });
+    }
+
+    if reader.byte()? != 0 {
+        return Err(DeserializeError::MissingLocations);
+    }
+
+    let encoding = String::from_utf8(reader.string()?).map_err(|_| DeserializeError::Invalid("encoding name is not valid UTF-8"))?;
+    let start_line = reader.varsint()?;
+
+    let length = reader.varuint()?;
+    let line_offsets = (0..length).map(|_| reader.varuint()).collect::<Result<_, _>>()?;
+
+    let length = reader.varuint()?;
+    let comments = (0..length)
+        .map(|_| {
+            let type_ = match reader.byte()? {
+                0 => CommentType::InlineComment,
+                1 => CommentType::EmbDocComment,
+                _ => return Err(DeserializeError::Invalid("unknown comment type")),
+            };
+            Ok(SerializedComment { type_, location: reader.location()? })
+        })
+        .collect::<Result<_, _>>()?;
+
+    let length = reader.varuint()?;
+    let magic_comments = (0..length).map(|_| Ok(SerializedMagicComment { key: reader.location()?, value: reader.location()? })).collect::<Result<_, _>>()?;
+
+    let data_loc = reader.optional_location()?;
+    let errors = reader.diagnostics(true)?;
+    let warnings = reader.diagnostics(false)?;
+    let continuable = reader.byte()? != 0;
+
+    reader.constant_pool_offset = reader.uint32()? as usize;
+    reader.constant_pool_size = reader.varuint()? as usize;
+
+    if reader.constant_pool_size.checked_mul(8).and_then(|size| size.checked_add(reader.constant_pool_offset)).is_none_or(|end| end > serialized.len()) {
+        return Err(DeserializeError::Invalid("constant pool is out of range"));
+    }
+
+    let node = reader.node()?;
+    if reader.offset != reader.constant_pool_offset {
+        return Err(DeserializeError::Invalid("syntax tree does not end at the constant pool"));
+    }
+
+    Ok(SerializedParseResult {
+        encoding,
+        start_line,
+        line_offsets,
+        comments,
+        magic_comments,
+        data_loc,
+        errors,
+        warnings,
+        continuable,
+        node,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{deserialize, DeserializeError, SerializedField};
+    use crate::{parse, CommentType, DiagnosticKind, ErrorLevel};
+
+    #[test]
+    fn test_deserialize() {
+        let source = b"# comment\nfoo = 1\nfoo\n";
+        let result = parse(source);
+        let serialized = deserialize(&result.serialize()).unwrap();
+
+        assert_eq!(serialized.encoding(), "UTF-8");
+        assert_eq!(serialized.start_line(), 1);
+        assert_eq!(serialized.line_offsets(), result.line_offsets());
+        assert_eq!(serialized.comments().len(), 1);
+        assert_eq!(serialized.comments()[0].type_(), CommentType::InlineComment);
+        assert!(serialized.errors().is_empty());
+
+        let program = serialized.node();
+        assert_eq!(program.name(), "ProgramNode");
+        assert_eq!(program.location().start(), 10);
+        assert_eq!(program.field("locals"), Some(&SerializedField::ConstantList(vec![b"foo".to_vec()])));
+
+        let statements = program.child_nodes()[0];
+        let body: Vec<&str> = statements.child_nodes().iter().map(|node| node.name()).collect();
+        assert_eq!(body, ["LocalVariableWriteNode", "LocalVariableReadNode"]);
+
+        let write = statements.child_nodes()[0];
+        assert_eq!(write.field("name"), Some(&SerializedField::Constant(b"foo".to_vec())));
+        assert_eq!(write.field("depth"), Some(&SerializedField::UInt32(0)));
+
+        let Some(SerializedField::Node(value)) = write.field("value") else { panic!("expected a value node") };
+        let Some(SerializedField::Integer(integer)) = value.field("value") else { panic!("expected an integer") };
+        assert_eq!(integer.to_u32_digits(), (false, [1].as_slice()));
+    }
+
+    #[test]
+    fn test_deserialize_node_ids() {
+        let result = parse(b"def foo(bar) = bar");
+        let serialized = deserialize(&result.serialize()).unwrap();
+
+        let def = serialized.node().child_nodes()[0].child_nodes()[0];
+        assert_eq!(def.name(), "DefNode");
+        assert_eq!(def.field("name"), Some(&SerializedField::Constant(b"foo".to_vec())));
+        assert_ne!(def.node_id(), serialized.node().node_id());
+    }
+
+    #[test]
+    fn test_deserialize_errors() {
+        let result = parse(b"alias $a $1");
+        let serialized = deserialize(&result.serialize()).unwrap();
+
+        let error = &serialized.errors()[0];
+        assert_eq!(error.kind(), DiagnosticKind::AliasArgumentNumberedReference);
+        assert_eq!(error.error_level(), Some(ErrorLevel::Syntax));
+        assert_eq!(error.message(), result.errors().next().unwrap().message());
+    }
+
+    #[test]
+    fn test_deserialize_invalid_header() {
+        assert_eq!(deserialize(b"MRISP"), Err(DeserializeError::InvalidHeader));
+    }
+
+    #[test]
+    fn test_deserialize_version_mismatch() {
+        let mut serialized = parse(b"1").serialize();
+        serialized[5] = serialized[5].wrapping_add(1);
+
+        assert!(matches!(deserialize(&serialized), Err(DeserializeError::VersionMismatch { .. })));
+    }
+
+    #[test]
+    fn test_deserialize_truncated() {
+        let serialized = parse(b"foo(1, 2, 3)").serialize();
+        assert_eq!(deserialize(&serialized[..20]), Err(DeserializeError::UnexpectedEof));
+    }
+}
@@ -0,0 +1,169 @@
+//! JSON serialization of the syntax tree.
+//!
+//! This module is a thin wrapper over prism's own JSON dumper, so the output
+//! matches the output of `Prism::Node#to_json` in the Ruby library.
+
+use ruby_prism_sys::pm_dump_json;
+
+use super::Buffer;
+use crate::{Node, ParseResult};
+
+impl Node<'_> {
+    /// Returns a JSON representation of this node and all of its children.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        let buffer = Buffer::new();
+        unsafe { pm_dump_json(buffer.as_ptr(), self.parser(), self.as_ptr()) };
+        String::from_utf8_lossy(buffer.as_bytes()).into_owned()
+    }
+}
+
+impl ParseResult<'_> {
+    /// Returns a JSON representation of the syntax tree.
+    #[must_use]
+    pub fn to_json(&self) -> String {
+        self.node().to_json()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+    use std::path::{Path, PathBuf};
+
+    use ruby_prism_sys::{PM_NODE_FLAG_NEWLINE, PM_NODE_FLAG_STATIC_LITERAL};
+    use serde_json::{json, Value};
+
+    use crate::{parse, FieldValue, Location, Node, NodeKind};
+
+    fn location_json(location: &Location<'_>) -> Value {
+        json!({ "start": location.start(), "length": location.end() - location.start() })
+    }
+
+    /// Asserts that the given JSON value describes the given node, with one
+    /// key for each field that prism's config lists for its type, and records
+    /// the kind of every node that was checked.
+    fn assert_node_json(node: &Node<'_>, value: &Value, kinds: &mut HashSet<NodeKind>) {
+        let name = format!("{:?}", node.kind());
+        kinds.insert(node.kind());
+
+        assert_eq!(value["type"], name);
+        assert_eq!(value["location"], location_json(&node.location()), "{name}");
+
+        let fields = node.fields();
+        let mut keys: Vec<&str> = value.as_object().unwrap().keys().map(String::as_str).collect();
+        let mut expected: Vec<&str> = ["type", "location"].into_iter().chain(fields.iter().map(|&(field, _)| field)).collect();
+        keys.sort_unstable();
+        expected.sort_unstable();
+        assert_eq!(keys, expected, "{name}");
+
+        for (field, field_value) in &fields {
+            let json = &value[*field];
+            match field_value {
+                FieldValue::Node(child) => assert_node_json(child, json, kinds),
+                FieldValue::OptionalNode(child) => match child {
+                    Some(child) => assert_node_json(child, json, kinds),
+                    None => assert!(json.is_null(), "{name}.{field}"),
+                },
+                FieldValue::NodeList(list) => {
+                    let array = json.as_array().unwrap();
+                    assert_eq!(array.len(), list.len(), "{name}.{field}");
+                    for (child, json) in list.iter().zip(array) {
+                        assert_node_json(&child, json, kinds);
+                    }
+                },
+                FieldValue::Location(location) => assert_eq!(*json, location_json(location), "{name}.{field}"),
+                FieldValue::OptionalLocation(location) => assert_eq!(*json, location.as_ref().map_or(Value::Null, location_json), "{name}.{field}"),
+                FieldValue::Constant(constant) => {
+                    if let Ok(constant) = std::str::from_utf8(constant.as_slice()) {
+                        assert_eq!(*json, constant, "{name}.{field}");
+                    }
+                },
+                FieldValue::OptionalConstant(constant) => assert_eq!(json.is_null(), constant.is_none(), "{name}.{field}"),
+                FieldValue::ConstantList(list) => assert_eq!(json.as_array().unwrap().len(), list.len(), "{name}.{field}"),
+                FieldValue::String(_) => assert!(json.is_string(), "{name}.{field}"),
+                FieldValue::UInt8(number) => assert_eq!(*json, *number, "{name}.{field}"),
+                FieldValue::UInt32(number) => assert_eq!(*json, *number, "{name}.{field}"),
+                FieldValue::Integer(_) | FieldValue::Double(_) => assert!(json.is_number(), "{name}.{field}"),
+                FieldValue::Flags(flags) => {
+                    // The JSON only lists the flags of the node's own type, not
+                    // the flags that are shared by every node.
+                    let own = flags & !(PM_NODE_FLAG_NEWLINE | PM_NODE_FLAG_STATIC_LITERAL);
+                    assert_eq!(json.as_array().unwrap().len(), own.count_ones() as usize, "{name}");
+                },
+            }
+        }
+    }
+
+    /// Collects every fixture file beneath the given directory.
+    fn collect_fixtures(directory: &Path, fixtures: &mut Vec<PathBuf>) {
+        for entry in std::fs::read_dir(directory).unwrap() {
+            let path = entry.unwrap().path();
+            if path.is_dir() {
+                collect_fixtures(&path, fixtures);
+            } else if path.extension().is_some_and(|extension| extension == "txt") {
+                fixtures.push(path);
+            }
+        }
+    }
+
+    #[test]
+    fn test_to_json() {
+        let result = parse(b"foo(1)");
+        let value: Value = serde_json::from_str(&result.to_json()).unwrap();
+
+        assert_eq!(value["type"], "ProgramNode");
+        assert_eq!(value["statements"]["body"][0]["type"], "CallNode");
+        assert_eq!(value["statements"]["body"][0]["arguments"]["arguments"][0]["value"], 1);
+    }
+
+    #[test]
+    fn test_node_to_json() {
+        let result = parse(b"foo(1)");
+        let node = result.node().as_program_node().unwrap().statements().body().iter().next().unwrap();
+        let value: Value = serde_json::from_str(&node.to_json()).unwrap();
+
+        assert_eq!(value["type"], "CallNode");
+        assert_eq!(value["location"]["start"], 0);
+        assert_eq!(value["location"]["length"], 6);
+    }
+
+    #[test]
+    fn test_to_json_fields() {
+        let result = parse(b"foo(1 +)");
+        let value: Value = serde_json::from_str(&result.to_json()).unwrap();
+
+        let mut kinds = HashSet::new();
+        assert_node_json(&result.node(), &value, &mut kinds);
+        assert!(kinds.contains(&NodeKind::ErrorRecoveryNode));
+    }
+
+    #[test]
+    fn test_to_json_every_node_type() {
+        // The fixtures live in the prism repository rather than in this crate,
+        // so there is nothing to check when the crate is built on its own.
+        let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("../../test/prism/fixtures");
+        if !directory.is_dir() {
+            return;
+        }
+
+        let mut fixtures = Vec::new();
+        collect_fixtures(&directory, &mut fixtures);
+
+        // Error recovery nodes are only created for syntax errors,
+        // which the fixtures do not contain.
+        let mut sources: Vec<Vec<u8>> = fixtures.iter().map(|fixture| std::fs::read(fixture).unwrap()).collect();
+        sources.push(b"foo(1 +)".to_vec());
+
+        let mut kinds = HashSet::new();
+        for source in &sources {
+            let result = parse(source);
+            let value: Value = serde_json::from_str(&result.to_json()).unwrap();
+            assert_node_json(&result.node(), &value, &mut kinds);
+        }
+
+        for kind in NodeKind::ALL {
+            assert!(kinds.contains(&kind), "{kind:?} was not found in any JSON output");
+        }
+    }
+}
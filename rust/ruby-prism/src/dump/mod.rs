@@ -0,0 +1,44 @@
+//! Dumping the syntax tree into other formats using prism's own dumpers.
+
+mod json;
+mod prettyprint;
+mod serialize;
+
+pub use self::prettyprint::Pretty;
+
+use ruby_prism_sys::{pm_buffer_free, pm_buffer_length, pm_buffer_new, pm_buffer_t, pm_buffer_value};
+
+/// An owned `pm_buffer_t` that is freed when dropped.
+struct Buffer {
+    raw: *mut pm_buffer_t,
+}
+
+impl Buffer {
+    /// Allocates a new empty buffer.
+    fn new() -> Self {
+        Self { raw: unsafe { pm_buffer_new() } }
+    }
+
+    /// Returns a pointer to the underlying buffer for prism to write into.
+    const fn as_ptr(&self) -> *mut pm_buffer_t {
+        self.raw
+    }
+
+    /// Returns the bytes that have been written into the buffer.
+    fn as_bytes(&self) -> &[u8] {
+        unsafe {
+            let length = pm_buffer_length(self.raw);
+            if length == 0 {
+                &[]
+            } else {
+                std::slice::from_raw_parts(pm_buffer_value(self.raw).cast::<u8>(), length)
+            }
+        }
+    }
+}
+
+impl Drop for Buffer {
+    fn drop(&mut self) {
+        unsafe { pm_buffer_free(self.raw) };
+    }
+}
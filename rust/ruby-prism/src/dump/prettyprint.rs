@@ -0,0 +1,76 @@
+//! Pretty-printing of the syntax tree.
+//!
+//! This is a thin wrapper over prism's own pretty-printer. Its output has the
+//! same shape as the trees in the `snapshots/` directory, but it is not
+//! byte-for-byte identical: it draws the tree with ASCII characters, prints
+//! `nil` for missing values, and only prints node-specific flags.
+
+use std::fmt;
+
+use ruby_prism_sys::pm_prettyprint;
+
+use super::Buffer;
+use crate::{Node, ParseResult};
+
+/// A wrapper around a node that implements `Display` by pretty-printing the
+/// node and all of its children as a tree. Created by [`Node::pretty`].
+pub struct Pretty<'a, 'pr> {
+    node: &'a Node<'pr>,
+}
+
+impl fmt::Display for Pretty<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buffer = Buffer::new();
+        unsafe { pm_prettyprint(buffer.as_ptr(), self.node.parser(), self.node.as_ptr()) };
+        f.write_str(&String::from_utf8_lossy(buffer.as_bytes()))
+    }
+}
+
+impl<'pr> Node<'pr> {
+    /// Returns a wrapper around this node that can be used to display it as a
+    /// pretty-printed tree.
+    #[must_use]
+    pub const fn pretty(&self) -> Pretty<'_, 'pr> {
+        Pretty { node: self }
+    }
+}
+
+impl ParseResult<'_> {
+    /// Returns the syntax tree pretty-printed as a tree.
+    #[must_use]
+    pub fn pretty_print(&self) -> String {
+        self.node().pretty().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    #[test]
+    fn test_pretty_print() {
+        let result = parse(b"1");
+        let expected = "\
+@ ProgramNode (location: (1,0)-(1,1))
++-- locals: []
++-- statements:
+    @ StatementsNode (location: (1,0)-(1,1))
+    +-- body: (length: 1)
+        +-- @ IntegerNode (location: (1,0)-(1,1))
+            +-- IntegerBaseFlags: decimal
+            +-- value: 1
+";
+        assert_eq!(result.pretty_print(), expected);
+    }
+
+    #[test]
+    fn test_node_pretty() {
+        let result = parse(b"foo = 1");
+        let node = result.node().as_program_node().unwrap().statements().body().iter().next().unwrap();
+        let printed = format!("{}", node.pretty());
+
+        assert!(printed.starts_with("@ LocalVariableWriteNode (location: (1,0)-(1,7))\n"));
+        assert!(printed.contains("+-- name: :foo\n"));
+        assert!(printed.contains("+-- name_loc: (1,0)-(1,3) = \"foo\"\n"));
+    }
+}
@@ -0,0 +1,20 @@
+//! Binary serialization of parse results.
+
+use ruby_prism_sys::pm_serialize;
+
+use super::Buffer;
+use crate::ParseResult;
+
+impl ParseResult<'_> {
+    /// Serializes the parse result into prism's binary format, which is the
+    /// same format that the Ruby and Java bindings read. The output can be
+    /// read back with [`deserialize`](crate::deserialize) without reparsing,
+    /// as long as it is read by the same version of prism.
+    #[must_use]
+    pub fn serialize(&self) -> Vec<u8> {
+        let node = self.node();
+        let buffer = Buffer::new();
+        unsafe { pm_serialize(node.parser().cast_mut(), node.as_ptr(), buffer.as_ptr()) };
+        buffer.as_bytes().to_vec()
+    }
+}
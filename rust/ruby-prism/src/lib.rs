@@ -15,17 +15,25 @@ mod bindings {
     include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
 }
 
+mod deserialize;
+mod dump;
 mod node;
 mod node_ext;
+mod owned;
+mod parent_map;
 mod parse_result;
 
 use std::ffi::CString;
 use std::ptr::NonNull;
 
 pub use self::bindings::*;
-pub use self::node::{ConstantId, ConstantList, ConstantListIter, Integer, NodeList, NodeListIter};
+pub use self::deserialize::{deserialize, DeserializeError, SerializedComment, SerializedDiagnostic, SerializedField, SerializedInteger, SerializedLocation, SerializedMagicComment, SerializedNode, SerializedParseResult};
+pub use self::dump::Pretty;
+pub use self::node::{ConstantId, ConstantList, ConstantListIter, FieldValue, Integer, NodeId, NodeList, NodeListIter};
 pub use self::node_ext::{ConstantPathError, FullName};
-pub use self::parse_result::{Comment, CommentType, Comments, Diagnostic, Diagnostics, Location, MagicComment, MagicComments, ParseResult};
+pub use self::owned::{parse_file, parse_many, parse_stream, OwnedParseResult, SourceError};
+pub use self::parent_map::{Ancestors, ParentMap};
+pub use self::parse_result::{CodeUnits, Comment, CommentMap, CommentType, Comments, Diagnostic, Diagnostics, ErrorLevel, LexResult, LineIndex, Location, MagicComment, MagicComments, Newlines, NodeAt, ParseResult, Position, RewriteError, Rewriter, TextEdit, Token, Tokens, WarningLevel};
 
 use ruby_prism_sys::{
     pm_arena_new, pm_options_command_line_set, pm_options_encoding_locked_set, pm_options_encoding_set, pm_options_filepath_set, pm_options_free, pm_options_frozen_string_literal_set, pm_options_line_set, pm_options_main_script_set, pm_options_new, pm_options_partial_script_set,
@@ -251,6 +259,7 @@ impl Options {
     /// Panics if `filepath` or `encoding` contain interior null bytes.
     #[must_use]
     pub fn build(self) -> ParseOptions {
+        let origin = self.clone();
         let opts = unsafe { pm_options_new() };
 
         let c_filepath = self.filepath.map(|filepath| {
@@ -315,6 +324,7 @@ impl Options {
 
         ParseOptions {
             options: opts,
+            origin,
             _filepath: c_filepath,
             _encoding: c_encoding,
             _scopes: self.scopes,
@@ -326,6 +336,9 @@ impl Options {
 /// any [`ParseResult`] created with [`parse_with_options`].
 pub struct ParseOptions {
     options: *mut pm_options_t,
+    // The options that these were built from, kept so that they can be
+    // rebuilt when cloning.
+    origin: Options,
     // These CStrings back the constant pm_string_t values inside `options`.
     // They must not be dropped before `options` is freed.
     _filepath: Option<CString>,
@@ -334,6 +347,12 @@ pub struct ParseOptions {
     _scopes: Vec<Scope>,
 }
 
+impl Clone for ParseOptions {
+    fn clone(&self) -> Self {
+        self.origin.clone().build()
+    }
+}
+
 impl Drop for ParseOptions {
     fn drop(&mut self) {
         unsafe { pm_options_free(self.options) };
@@ -366,6 +385,8 @@ pub fn parse(source: &[u8]) -> ParseResult<'_> {
 /// Parses the given source string with the given options and returns a parse
 /// result. The `options` must outlive the returned `ParseResult`.
 ///
+/// Use [`OwnedParseResult::new`] to get a result that owns both instead.
+///
 /// # Panics
 ///
 /// Panics if the parser fails to initialize.
@@ -375,6 +396,128 @@ pub fn parse_with_options<'a>(source: &'a [u8], options: &'a ParseOptions) -> Pa
     unsafe { parse_impl(source, options.options) }
 }
 
+/// Initializes a parser, lexes the source, and returns the result.
+///
+/// # Safety
+///
+/// `options` must be a valid pointer to a `pm_options_t` or null.
+unsafe fn lex_impl(source: &[u8], options: *const pm_options_t) -> LexResult<'_> {
+    let arena = pm_arena_new();
+    let parser = pm_parser_new(arena, source.as_ptr(), source.len(), options);
+    LexResult::new(source, arena, parser)
+}
+
+/// Lexes the given source string and returns a lex result containing the
+/// tokens.
+///
+/// The tokens are collected while the full parser runs, so the lex result
+/// also holds the tree through [`LexResult::result`] and
+/// [`LexResult::node`], the same as `Prism.parse_lex` in the Ruby API.
+///
+/// # Panics
+///
+/// Panics if the parser fails to initialize.
+///
+#[must_use]
+pub fn lex(source: &[u8]) -> LexResult<'_> {
+    unsafe { lex_impl(source, std::ptr::null()) }
+}
+
+/// Lexes the given source string with the given options and returns a lex
+/// result containing the tokens. The `options` must outlive the returned
+/// `LexResult`.
+///
+/// # Panics
+///
+/// Panics if the parser fails to initialize.
+///
+#[must_use]
+pub fn lex_with_options<'a>(source: &'a [u8], options: &'a ParseOptions) -> LexResult<'a> {
+    unsafe { lex_impl(source, options.options) }
+}
+
+// Thread safety
+//
+// Every type that points into a parse result holds raw pointers to the parser
+// and to nodes in its arena, which opts them out of `Send` and `Sync`. Once
+// `pm_parse` returns, nothing in this crate writes through those pointers:
+// every accessor only reads the finished tree, the constant pool, and the
+// lists hanging off the parser, and prism keeps no global mutable state that
+// they touch. The arena and parser are plain heap allocations, so they can be
+// freed from any thread. That makes it sound to share a parse result across
+// threads or to move it to another one, and the borrowing types below cannot
+// outlive the result because of their lifetimes. The generated node types get
+// the same implementations in `build.rs`.
+//
+// `ParseOptions` is only read by the parser after it has been built, so it can
+// be shared and moved as well.
+
+unsafe impl Send for ParseOptions {}
+unsafe impl Sync for ParseOptions {}
+
+unsafe impl Send for ParseResult<'_> {}
+unsafe impl Sync for ParseResult<'_> {}
+
+unsafe impl Send for Location<'_> {}
+unsafe impl Sync for Location<'_> {}
+
+unsafe impl Send for Node<'_> {}
+unsafe impl Sync for Node<'_> {}
+
+unsafe impl Send for NodeList<'_> {}
+unsafe impl Sync for NodeList<'_> {}
+
+unsafe impl Send for NodeListIter<'_> {}
+unsafe impl Sync for NodeListIter<'_> {}
+
+unsafe impl Send for ConstantId<'_> {}
+unsafe impl Sync for ConstantId<'_> {}
+
+unsafe impl Send for ConstantList<'_> {}
+unsafe impl Sync for ConstantList<'_> {}
+
+unsafe impl Send for ConstantListIter<'_> {}
+unsafe impl Sync for ConstantListIter<'_> {}
+
+unsafe impl Send for Integer<'_> {}
+unsafe impl Sync for Integer<'_> {}
+
+unsafe impl Send for Comment<'_> {}
+unsafe impl Sync for Comment<'_> {}
+
+unsafe impl Send for Comments<'_> {}
+unsafe impl Sync for Comments<'_> {}
+
+unsafe impl Send for MagicComment<'_> {}
+unsafe impl Sync for MagicComment<'_> {}
+
+unsafe impl Send for MagicComments<'_> {}
+unsafe impl Sync for MagicComments<'_> {}
+
+unsafe impl Send for Diagnostic<'_> {}
+unsafe impl Sync for Diagnostic<'_> {}
+
+unsafe impl Send for Diagnostics<'_> {}
+unsafe impl Sync for Diagnostics<'_> {}
+
+unsafe impl Send for Token<'_> {}
+unsafe impl Sync for Token<'_> {}
+
+unsafe impl Send for Tokens<'_> {}
+unsafe impl Sync for Tokens<'_> {}
+
+unsafe impl Send for CommentMap<'_> {}
+unsafe impl Sync for CommentMap<'_> {}
+
+unsafe impl Send for Newlines<'_> {}
+unsafe impl Sync for Newlines<'_> {}
+
+unsafe impl Send for ParentMap<'_> {}
+unsafe impl Sync for ParentMap<'_> {}
+
+unsafe impl Send for Ancestors<'_, '_> {}
+unsafe impl Sync for Ancestors<'_, '_> {}
+
 #[cfg(test)]
 mod tests {
     use super::parse;
@@ -1031,6 +1174,227 @@ end
         assert_eq!(5, visitor.max_depth);
     }
 
+    #[test]
+    fn try_visitor_break_test() {
+        use std::ops::ControlFlow;
+
+        use crate::{CallNode, Node, Skip, TryVisit};
+
+        #[derive(Default)]
+        struct EvalFinder {
+            calls: usize,
+        }
+
+        impl<'pr> TryVisit<'pr> for EvalFinder {
+            type Break = Node<'pr>;
+
+            fn visit_call_node(&mut self, node: &CallNode<'pr>) -> ControlFlow<Self::Break, Skip> {
+                self.calls += 1;
+                if node.name().as_slice() == b"eval" {
+                    ControlFlow::Break(node.as_node())
+                } else {
+                    ControlFlow::Continue(Skip::Nothing)
+                }
+            }
+        }
+
+        let source = "foo(bar)\neval(baz)\nqux\neval(quux)\n";
+        let result = parse(source.as_ref());
+        let mut visitor = EvalFinder::default();
+
+        let ControlFlow::Break(call) = visitor.visit(&result.node()) else { panic!("Expected to find an eval call") };
+        assert_eq!(call.location().as_slice(), b"eval(baz)");
+        assert_eq!(visitor.calls, 3);
+    }
+
+    #[test]
+    fn try_visitor_skip_test() {
+        use std::ops::ControlFlow;
+
+        use crate::{CallNode, DefNode, Skip, TryVisit};
+
+        #[derive(Default)]
+        struct CallCounter {
+            calls: usize,
+        }
+
+        impl<'pr> TryVisit<'pr> for CallCounter {
+            type Break = ();
+
+            fn visit_def_node(&mut self, _node: &DefNode<'pr>) -> ControlFlow<Self::Break, Skip> {
+                ControlFlow::Continue(Skip::Children)
+            }
+
+            fn visit_call_node(&mut self, _node: &CallNode<'pr>) -> ControlFlow<Self::Break, Skip> {
+                self.calls += 1;
+                ControlFlow::Continue(Skip::Nothing)
+            }
+        }
+
+        let source = "foo(bar)\ndef baz\n  qux(quux)\nend\n";
+        let result = parse(source.as_ref());
+        let mut visitor = CallCounter::default();
+
+        assert_eq!(visitor.visit(&result.node()), ControlFlow::Continue(()));
+        assert_eq!(visitor.calls, 2);
+    }
+
+    #[test]
+    fn children_test() {
+        let result = parse(b"foo.bar(1, 2) { baz }");
+        let call = result.node().as_program_node().unwrap().statements().body().iter().next().unwrap();
+
+        let children: Vec<&[u8]> = call.children().map(|child| child.location().as_slice()).collect();
+        assert_eq!(children, [&b"foo"[..], b"1, 2", b"{ baz }"]);
+
+        let arguments = call.children().nth(1).unwrap();
+        assert_eq!(arguments.children().count(), 2);
+        assert_eq!(arguments.children().next().unwrap().children().count(), 0);
+    }
+
+    #[test]
+    fn child_nodes_with_names_test() {
+        let result = parse(b"foo(1, 2)");
+        let call = result.node().as_program_node().unwrap().statements().body().iter().next().unwrap();
+
+        let children: Vec<(&str, Option<&[u8]>)> = call.child_nodes_with_names().map(|(name, child)| (name, child.map(|child| child.location().as_slice()))).collect();
+        assert_eq!(children, [("receiver", None), ("arguments", Some(&b"1, 2"[..])), ("block", None)]);
+
+        let arguments = call.as_call_node().unwrap().arguments().unwrap().as_node();
+        let names: Vec<&str> = arguments.child_nodes_with_names().map(|(name, _)| name).collect();
+        assert_eq!(names, ["arguments", "arguments"]);
+    }
+
+    #[test]
+    fn fields_test() {
+        use crate::FieldValue;
+
+        let result = parse(b"foo(1)");
+        let call = result.node().as_program_node().unwrap().statements().body().iter().next().unwrap();
+        let fields = call.fields();
+
+        let names: Vec<&str> = fields.iter().map(|(name, _)| *name).collect();
+        assert_eq!(names, ["flags", "receiver", "call_operator_loc", "name", "message_loc", "opening_loc", "arguments", "closing_loc", "equal_loc", "block"]);
+
+        assert!(matches!(fields[0].1, FieldValue::Flags(_)));
+        assert!(matches!(fields[1].1, FieldValue::OptionalNode(None)));
+        assert!(matches!(fields[2].1, FieldValue::OptionalLocation(None)));
+        assert!(matches!(&fields[3].1, FieldValue::Constant(name) if name.as_slice() == b"foo"));
+        assert!(matches!(&fields[5].1, FieldValue::OptionalLocation(Some(location)) if location.as_slice() == b"("));
+        assert!(matches!(&fields[6].1, FieldValue::OptionalNode(Some(node)) if node.as_arguments_node().is_some()));
+    }
+
+    #[test]
+    fn fields_values_test() {
+        use crate::FieldValue;
+
+        let result = parse(b"'abc'; 1.5; [a = 1]; nil");
+        let body: Vec<_> = result.node().as_program_node().unwrap().statements().body().iter().collect();
+
+        let string = body[0].fields();
+        assert!(matches!(string.last().unwrap(), ("unescaped", FieldValue::String(b"abc"))));
+
+        let float = body[1].fields();
+        assert!(matches!(float.last().unwrap(), ("value", FieldValue::Double(value)) if (*value - 1.5).abs() < f64::EPSILON));
+
+        let array = body[2].fields();
+        assert!(matches!(&array[1], ("elements", FieldValue::NodeList(elements)) if elements.len() == 1));
+
+        assert!(body[3].fields().is_empty());
+    }
+
+    #[test]
+    fn node_kind_test() {
+        use std::collections::HashMap;
+
+        use crate::NodeKind;
+
+        let result = parse(b"foo(1, 2)\nbar");
+        let statements = result.node().as_program_node().unwrap().statements();
+
+        let mut counts: HashMap<NodeKind, usize> = HashMap::new();
+        for node in &statements.body() {
+            *counts.entry(node.kind()).or_default() += 1;
+        }
+
+        assert_eq!(counts[&NodeKind::CallNode], 2);
+        assert_eq!(result.node().kind(), NodeKind::ProgramNode);
+        assert_eq!(NodeKind::CallNode.name(), "PM_CALL_NODE");
+        assert_eq!(NodeKind::ProgramNode.name(), "PM_PROGRAM_NODE");
+    }
+
+    #[test]
+    fn node_kind_all_test() {
+        use crate::NodeKind;
+
+        assert!(NodeKind::ALL.windows(2).all(|pair| pair[0] < pair[1]));
+        assert!(NodeKind::ALL.iter().all(|kind| kind.name().starts_with("PM_") && kind.name().ends_with("_NODE")));
+        assert_eq!(NodeKind::ALL[0], NodeKind::AliasGlobalVariableNode);
+    }
+
+    #[test]
+    fn node_id_test() {
+        use std::collections::HashSet;
+
+        let result = parse(b"foo(bar)\nfoo(bar)");
+        let statements = result.node().as_program_node().unwrap().statements();
+        let body = statements.body();
+        let calls: Vec<_> = body.iter().collect();
+
+        assert_eq!(calls[0], body.iter().next().unwrap());
+        assert_ne!(calls[0], calls[1]);
+        assert_ne!(calls[0].id(), calls[1].id());
+        assert_eq!(calls[0].id(), calls[0].as_call_node().unwrap().id());
+
+        let first = calls[0].as_call_node().unwrap();
+        assert_eq!(first, body.iter().next().unwrap().as_call_node().unwrap());
+        assert_ne!(first, calls[1].as_call_node().unwrap());
+
+        let ids: HashSet<_> = result.node().children().map(|node| node.id()).collect();
+        assert_eq!(ids.len(), 1);
+
+        let nodes: HashSet<_> = body.iter().chain(body.iter()).collect();
+        assert_eq!(nodes.len(), 2);
+
+        let other = parse(b"foo(bar)\nfoo(bar)");
+        assert_ne!(result.node(), other.node());
+    }
+
+    #[test]
+    fn structurally_eq_test() {
+        let result = parse(b"foo(bar, 1, :baz)\n  foo( bar,1,:baz )\nfoo(bar, 2, :baz)\nfoo(bar, 1, :qux)\nfoo bar, 1, :baz");
+        let statements = result.node().as_program_node().unwrap().statements();
+        let calls: Vec<_> = statements.body().iter().collect();
+
+        assert!(calls[0].structurally_eq(&calls[1]));
+        assert_eq!(calls[0].structural_hash(), calls[1].structural_hash());
+        assert!(!calls[0].structurally_eq(&calls[2]));
+        assert!(!calls[0].structurally_eq(&calls[3]));
+        assert!(!calls[0].structurally_eq(&calls[4]));
+        assert_ne!(calls[0].structural_hash(), calls[2].structural_hash());
+
+        let other = parse(b"x = 1\nfoo(bar, 1, :baz)");
+        let statements = other.node().as_program_node().unwrap().statements();
+        let call = statements.body().last().unwrap();
+
+        assert!(call.structurally_eq(&calls[0]));
+        assert_eq!(call.structural_hash(), calls[0].structural_hash());
+        assert!(!statements.body().first().unwrap().structurally_eq(&calls[0]));
+    }
+
+    #[test]
+    fn structurally_eq_values_test() {
+        let result = parse(b"[1.5, 1.5, 2.5, 123456789012345678901234567890, 123456789012345678901234567890, 123456789012345678901234567891, \"a\", \"a\", \"b\"]");
+        let array = result.node().as_program_node().unwrap().statements().body().first().unwrap();
+        let elements: Vec<_> = array.as_array_node().unwrap().elements().iter().collect();
+
+        for pair in elements.chunks(3) {
+            assert!(pair[0].structurally_eq(&pair[1]));
+            assert!(!pair[0].structurally_eq(&pair[2]));
+            assert_eq!(pair[0].structural_hash(), pair[1].structural_hash());
+        }
+    }
+
     #[test]
     fn integer_value_test() {
         let result = parse("0xA".as_ref());
@@ -1160,6 +1524,56 @@ end
         assert!(stmt.as_local_variable_read_node().is_some());
     }
 
+    #[test]
+    fn lex_test() {
+        use super::{lex, TokenKind};
+
+        let result = lex(b"foo + 1 # comment\n");
+        assert!(result.is_success());
+
+        let kinds: Vec<TokenKind> = result.tokens().map(|token| token.kind()).collect();
+        assert_eq!(kinds, [TokenKind::Identifier, TokenKind::Plus, TokenKind::Integer, TokenKind::Comment, TokenKind::Newline, TokenKind::Eof]);
+
+        let token = result.tokens().nth(2).unwrap();
+        assert_eq!(token.text(), b"1");
+        assert_eq!(token.location().start(), 6);
+        assert_eq!(token.location().end(), 7);
+        assert_eq!(token.kind().name(), "INTEGER");
+    }
+
+    #[test]
+    fn lex_with_options_test() {
+        use super::{lex_with_options, Options, TokenKind};
+
+        let options = Options::default().line(10).build();
+        let result = lex_with_options(b"foo\n__END__\nbar\n", &options);
+
+        let tokens: Vec<_> = result.tokens().collect();
+        assert_eq!(tokens.first().unwrap().location().start_line(), 10);
+        assert!(tokens.iter().any(|token| token.kind() == TokenKind::End));
+        assert!(result.data_loc().is_some());
+    }
+
+    #[test]
+    fn lex_node_test() {
+        use super::{lex, TokenKind};
+
+        let source = "# comment\nfoo(<<~EOS)\n  heredoc\nEOS\n__END__\ndata\n";
+        let result = lex(source.as_ref());
+        assert!(result.result().is_success());
+
+        let call = result.node().as_program_node().unwrap().statements().body().iter().next().unwrap();
+        assert!(call.as_call_node().is_some());
+
+        let kinds: Vec<TokenKind> = result.tokens().map(|token| token.kind()).collect();
+        for kind in [TokenKind::Comment, TokenKind::HeredocStart, TokenKind::StringContent, TokenKind::HeredocEnd, TokenKind::End] {
+            assert!(kinds.contains(&kind), "missing {kind:?}");
+        }
+
+        let heredoc_end = result.tokens().find(|token| token.kind() == TokenKind::HeredocEnd).unwrap();
+        assert_eq!(heredoc_end.text(), b"EOS\n");
+    }
+
     #[test]
     fn malformed_shebang() {
         let source = "#!\x00";
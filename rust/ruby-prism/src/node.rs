@@ -1,16 +1,17 @@
 //! Node-related types for the prism parser.
 //!
 //! This module contains types for working with AST nodes, including node lists,
-//! constant IDs, and integer values.
+//! constant IDs, node identifiers, and integer values.
 
+use std::hash::{Hash, Hasher};
 use std::marker::PhantomData;
 use std::ptr::NonNull;
 
-use ruby_prism_sys::{pm_constant_id_list_t, pm_constant_id_t, pm_integer_t, pm_node_list, pm_node_t, pm_parser_t};
+use ruby_prism_sys::{pm_constant_id_list_t, pm_constant_id_t, pm_integer_t, pm_node_flags_t, pm_node_list, pm_node_t, pm_parser_t};
 
 // Note: The `Node` enum is defined in the generated `bindings.rs` file.
 // We import it here via `crate::Node` to avoid circular dependencies.
-use crate::Node;
+use crate::{Location, Node};
 
 // ============================================================================
 // NodeList
@@ -93,6 +94,21 @@ impl<'pr> NodeList<'pr> {
             Some(unsafe { self.at(self.len() - 1) })
         }
     }
+
+    /// Returns true if both lists have the same length and their nodes are
+    /// structurally equal pairwise.
+    pub(crate) fn structurally_eq(&self, other: &NodeList<'_>) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(left, right)| left.structurally_eq(&right))
+    }
+
+    /// Feeds the length of the list and the structure of its nodes into the
+    /// given hasher.
+    pub(crate) fn hash_structure<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for node in self {
+            node.hash_structure(state);
+        }
+    }
 }
 
 impl<'pr> IntoIterator for &NodeList<'pr> {
@@ -229,6 +245,21 @@ impl<'pr> ConstantList<'pr> {
             Some(unsafe { self.at(self.len() - 1) })
         }
     }
+
+    /// Returns true if both lists contain the same constants in the same
+    /// order, compared by their bytes.
+    pub(crate) fn structurally_eq(&self, other: &ConstantList<'_>) -> bool {
+        self.iter().map(|constant| constant.as_slice()).eq(other.iter().map(|constant| constant.as_slice()))
+    }
+
+    /// Feeds the length of the list and the bytes of its constants into the
+    /// given hasher.
+    pub(crate) fn hash_structure<H: Hasher>(&self, state: &mut H) {
+        self.len().hash(state);
+        for constant in self {
+            constant.as_slice().hash(state);
+        }
+    }
 }
 
 impl<'pr> IntoIterator for &ConstantList<'pr> {
@@ -302,3 +333,90 @@ impl TryInto<i32> for Integer<'_> {
         }
     }
 }
+
+// ============================================================================
+// NodeId
+// ============================================================================
+
+/// The identifier prism assigns to a node.
+///
+/// Identifiers are unique among the nodes of a single parse result, but those
+/// from different parse results can collide, so compare nodes directly to tell
+/// whether they are the same node.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct NodeId(u32);
+
+impl NodeId {
+    /// Creates a new node identifier from the `node_id` of a prism node.
+    pub(crate) const fn new(id: u32) -> Self {
+        Self(id)
+    }
+
+    /// Returns the raw identifier, which matches `Prism::Node#node_id` in the
+    /// Ruby API.
+    #[must_use]
+    pub const fn get(self) -> u32 {
+        self.0
+    }
+}
+
+impl Node<'_> {
+    /// Returns the identifier of this node.
+    #[must_use]
+    pub fn id(&self) -> NodeId {
+        NodeId::new(unsafe { (*self.as_ptr()).node_id })
+    }
+}
+
+// Nodes are equal when they are the same node in the same tree.
+impl PartialEq for Node<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_ptr() == other.as_ptr()
+    }
+}
+
+impl Eq for Node<'_> {}
+
+impl Hash for Node<'_> {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_ptr().hash(state);
+    }
+}
+
+// ============================================================================
+// FieldValue
+// ============================================================================
+
+/// The value of a single field on a node, as returned by [`Node::fields`].
+/// There is one variant for each kind of field in the prism config.
+#[derive(Debug)]
+pub enum FieldValue<'pr> {
+    /// A child node that is always present.
+    Node(Node<'pr>),
+    /// A child node that may be missing.
+    OptionalNode(Option<Node<'pr>>),
+    /// A list of child nodes.
+    NodeList(NodeList<'pr>),
+    /// A string of bytes, usually the unescaped contents of a literal.
+    String(&'pr [u8]),
+    /// A constant from the constant pool.
+    Constant(ConstantId<'pr>),
+    /// A constant from the constant pool that may be missing.
+    OptionalConstant(Option<ConstantId<'pr>>),
+    /// A list of constants from the constant pool.
+    ConstantList(ConstantList<'pr>),
+    /// A location in the source.
+    Location(Location<'pr>),
+    /// A location in the source that may be missing.
+    OptionalLocation(Option<Location<'pr>>),
+    /// An 8-bit unsigned integer.
+    UInt8(u8),
+    /// A 32-bit unsigned integer.
+    UInt32(u32),
+    /// An arbitrarily-sized integer.
+    Integer(Integer<'pr>),
+    /// A floating point number.
+    Double(f64),
+    /// The flags set on the node, including the flags shared by every node.
+    Flags(pm_node_flags_t),
+}
@@ -0,0 +1,564 @@
+//! Parse results that own their source and options.
+//!
+//! [`ParseResult`] borrows the source it was parsed from, which makes it hard
+//! to return from functions that read the source themselves. The types in this
+//! module keep the source and the options alive for as long as the result.
+
+use std::ffi::{c_char, c_int, c_void, CString};
+use std::fmt;
+use std::io::BufRead;
+use std::path::Path;
+use std::ptr::NonNull;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use ruby_prism_sys::{pm_arena_new, pm_parse_stream, pm_parser_t, pm_source_free, pm_source_init_result_t, pm_source_length, pm_source_mapped_new, pm_source_source, pm_source_stream_new, pm_source_t, PM_SOURCE_INIT_ERROR_DIRECTORY, PM_SOURCE_INIT_ERROR_NON_REGULAR, PM_SOURCE_INIT_SUCCESS};
+
+use crate::{parse_impl, Node, Options, ParseOptions, ParseResult};
+
+/// An error that occurred while reading a source file.
+#[derive(Debug)]
+pub enum SourceError {
+    /// The file could not be opened, inspected, or mapped into memory.
+    Io(std::io::Error),
+    /// The path refers to a directory.
+    Directory,
+    /// The path refers to something other than a regular file, such as a pipe
+    /// or a character device. These should be read through
+    /// [`std::io::Read`] and parsed with [`crate::parse_with_options`] instead.
+    NonRegular,
+}
+
+impl fmt::Display for SourceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(error) => {
+                write!(f, "Failed to read source file: {error}")
+            },
+            Self::Directory => {
+                write!(f, "Source file is a directory")
+            },
+            Self::NonRegular => {
+                write!(f, "Source file is not a regular file")
+            },
+        }
+    }
+}
+
+impl std::error::Error for SourceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Self::Io(error) => Some(error),
+            Self::Directory | Self::NonRegular => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for SourceError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io(error)
+    }
+}
+
+/// A source buffer that is owned by a parse result.
+enum Source {
+    /// A source that was allocated by prism, such as a mapped file.
+    Prism(NonNull<pm_source_t>),
+    /// A source that was allocated by Rust and handed over to the result.
+    Boxed(NonNull<[u8]>),
+}
+
+impl Source {
+    /// Returns the bytes of the source. The slice is valid for as long as the
+    /// source is alive, which callers must uphold themselves.
+    unsafe fn as_bytes(&self) -> &'static [u8] {
+        match self {
+            Self::Prism(raw) => {
+                let length = pm_source_length(raw.as_ptr());
+                if length == 0 {
+                    &[]
+                } else {
+                    std::slice::from_raw_parts(pm_source_source(raw.as_ptr()), length)
+                }
+            },
+            Self::Boxed(bytes) => &*bytes.as_ptr(),
+        }
+    }
+}
+
+impl Drop for Source {
+    fn drop(&mut self) {
+        match self {
+            Self::Prism(raw) => unsafe { pm_source_free(raw.as_ptr()) },
+            Self::Boxed(bytes) => drop(unsafe { Box::from_raw(bytes.as_ptr()) }),
+        }
+    }
+}
+
+// A source is only read once it has been parsed, and freeing a stream source
+// does not call back into the reader it was created with, so it can be shared
+// and moved just like the parse result that points into it.
+unsafe impl Send for Source {}
+unsafe impl Sync for Source {}
+
+/// The result of parsing a source that is owned by the result itself, along
+/// with the options that it was parsed with.
+pub struct OwnedParseResult {
+    // The fields are dropped in declaration order, so the parse result is
+    // freed before the source and the options that it points into.
+    result: ParseResult<'static>,
+    options: ParseOptions,
+    _source: Source,
+}
+
+impl OwnedParseResult {
+    /// Parses the given source with the given options, taking ownership of
+    /// both so that the result is not tied to either of their lifetimes.
+    #[must_use]
+    pub fn new(source: impl Into<Box<[u8]>>, options: ParseOptions) -> Self {
+        // The box is turned into a raw pointer so that moving the source into
+        // the result does not invalidate the slice the parser points into.
+        let source = Source::Boxed(NonNull::from(Box::leak(source.into())));
+        let result = unsafe { parse_impl(source.as_bytes(), options.options) };
+        Self { result, options, _source: source }
+    }
+
+    /// Returns the parse result, borrowed from this owned result.
+    #[must_use]
+    pub const fn result(&self) -> &ParseResult<'_> {
+        &self.result
+    }
+
+    /// Returns the source that was parsed.
+    #[must_use]
+    pub const fn source(&self) -> &[u8] {
+        self.result.source()
+    }
+
+    /// Returns the options that the source was parsed with.
+    #[must_use]
+    pub const fn options(&self) -> &ParseOptions {
+        &self.options
+    }
+
+    /// Returns the root node of the parse result.
+    #[must_use]
+    pub fn node(&self) -> Node<'_> {
+        self.result.node()
+    }
+}
+
+impl fmt::Debug for OwnedParseResult {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedParseResult").field("result", &self.result).finish_non_exhaustive()
+    }
+}
+
+/// Memory-maps the file at the given path and parses it with the given
+/// options.
+///
+/// The returned result owns both the mapped source and a copy of the options,
+/// so it is not tied to the lifetime of either. Note that the file path used in
+/// diagnostics is the one from the options, not `path`.
+///
+/// # Errors
+///
+/// Returns [`SourceError::Directory`] or [`SourceError::NonRegular`] if the
+/// path does not refer to a regular file, and [`SourceError::Io`] if the file
+/// could not be opened or mapped.
+pub fn parse_file(path: impl AsRef<Path>, options: &ParseOptions) -> Result<OwnedParseResult, SourceError> {
+    parse_file_impl(path.as_ref(), options.clone())
+}
+
+/// Memory-maps the file at the given path and parses it, taking ownership of
+/// the options.
+fn parse_file_impl(path: &Path, options: ParseOptions) -> Result<OwnedParseResult, SourceError> {
+    let filepath = CString::new(path.as_os_str().as_encoded_bytes()).map_err(|error| std::io::Error::new(std::io::ErrorKind::InvalidInput, error))?;
+
+    let mut init_result: pm_source_init_result_t = PM_SOURCE_INIT_SUCCESS;
+    let raw = unsafe { pm_source_mapped_new(filepath.as_ptr(), 0, &raw mut init_result) };
+
+    let source = match (init_result, NonNull::new(raw)) {
+        (PM_SOURCE_INIT_SUCCESS, Some(raw)) => Source::Prism(raw),
+        (PM_SOURCE_INIT_ERROR_DIRECTORY, _) => return Err(SourceError::Directory),
+        (PM_SOURCE_INIT_ERROR_NON_REGULAR, _) => return Err(SourceError::NonRegular),
+        _ => return Err(SourceError::Io(std::io::Error::last_os_error())),
+    };
+
+    let result = unsafe { parse_impl(source.as_bytes(), options.options) };
+    Ok(OwnedParseResult { result, options, _source: source })
+}
+
+/// Parses each of the files at the given paths in parallel and returns their
+/// results in the same order as the paths.
+///
+/// The files are spread across one worker thread per available CPU. Every
+/// result is parsed into its own arena, so workers never share any parser
+/// state. If the options do not set a file path, each result uses the path of
+/// its own file so that diagnostics point at the right place.
+///
+/// # Panics
+///
+/// Panics if a worker thread panics.
+pub fn parse_many<P: AsRef<Path> + Sync>(paths: &[P], options: &Options) -> Vec<Result<OwnedParseResult, SourceError>> {
+    let shared = options.filepath.is_some().then(|| options.clone().build());
+    let parse = |path: &Path| {
+        let options = shared.as_ref().map_or_else(|| options.clone().filepath(&path.to_string_lossy()).build(), Clone::clone);
+        parse_file_impl(path, options)
+    };
+
+    let workers = std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get).min(paths.len());
+    if workers <= 1 {
+        return paths.iter().map(|path| parse(path.as_ref())).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let mut results: Vec<Option<Result<OwnedParseResult, SourceError>>> = std::iter::repeat_with(|| None).take(paths.len()).collect();
+
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut parsed = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(path) = paths.get(index) else { break parsed };
+                        parsed.push((index, parse(path.as_ref())));
+                    }
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            for (index, result) in handle.join().expect("parse_many worker thread panicked.") {
+                results[index] = Some(result);
+            }
+        }
+    });
+
+    results.into_iter().map(|result| result.expect("every path is parsed by a worker.")).collect()
+}
+
+/// The state that is passed through to the stream callbacks.
+struct Stream<R> {
+    reader: R,
+    eof: bool,
+    error: Option<std::io::Error>,
+}
+
+impl<R: BufRead> Stream<R> {
+    /// Reads bytes into the given buffer until it is full, a newline has been
+    /// read, or the reader is exhausted. Returns the number of bytes read.
+    fn read_line(&mut self, buffer: &mut [u8]) -> usize {
+        let mut written = 0;
+
+        while written < buffer.len() {
+            let available = match self.reader.fill_buf() {
+                Ok(available) => available,
+                Err(error) if error.kind() == std::io::ErrorKind::Interrupted => continue,
+                Err(error) => {
+                    self.error = Some(error);
+                    self.eof = true;
+                    break;
+                },
+            };
+
+            if available.is_empty() {
+                self.eof = true;
+                break;
+            }
+
+            let length = available.len().min(buffer.len() - written);
+            let (length, newline) = available[..length].iter().position(|&byte| byte == b'\n').map_or((length, false), |index| (index + 1, true));
+
+            buffer[written..written + length].copy_from_slice(&available[..length]);
+            self.reader.consume(length);
+            written += length;
+
+            if newline {
+                break;
+            }
+        }
+
+        written
+    }
+}
+
+// C callback that reads a line from a Rust reader with the semantics of fgets
+unsafe extern "C" fn stream_fgets<R: BufRead>(string: *mut c_char, size: c_int, stream: *mut c_void) -> *mut c_char {
+    let stream = &mut *(stream.cast::<Stream<R>>());
+    let Ok(size) = usize::try_from(size) else { return std::ptr::null_mut() };
+
+    if size == 0 || stream.error.is_some() {
+        return std::ptr::null_mut();
+    }
+
+    // Leave room for the terminating null byte.
+    let buffer = std::slice::from_raw_parts_mut(string.cast::<u8>(), size);
+    let written = stream.read_line(&mut buffer[..size - 1]);
+
+    if written == 0 {
+        std::ptr::null_mut()
+    } else {
+        buffer[written] = 0;
+        string
+    }
+}
+
+// C callback that reports whether a Rust reader has been exhausted
+unsafe extern "C" fn stream_feof<R: BufRead>(stream: *mut c_void) -> c_int {
+    let stream = &*(stream.cast::<Stream<R>>());
+    c_int::from(stream.eof)
+}
+
+/// Reads Ruby source from the given reader and parses it with the given
+/// options.
+///
+/// Reading stops after a line containing only `__END__`, so any data that
+/// follows it is left unread in the reader. Pass the reader by mutable
+/// reference (for example `&mut std::io::stdin().lock()`) to consume the rest
+/// of it afterward. If the source fails to parse before the reader is
+/// exhausted, more lines are read and the source is parsed again, in case the
+/// marker was part of a heredoc or another literal.
+///
+/// # Panics
+///
+/// Panics if prism fails to allocate the stream source.
+///
+/// # Errors
+///
+/// Returns any error other than [`std::io::ErrorKind::Interrupted`] that the
+/// reader returns.
+pub fn parse_stream<R: BufRead>(reader: R, options: &ParseOptions) -> std::io::Result<OwnedParseResult> {
+    let mut stream = Stream { reader, eof: false, error: None };
+
+    let raw = unsafe { pm_source_stream_new((&raw mut stream).cast(), Some(stream_fgets::<R>), Some(stream_feof::<R>)) };
+    let raw = NonNull::new(raw).expect("prism failed to allocate a stream source.");
+    let source = Source::Prism(raw);
+
+    let options = options.clone();
+    let arena = unsafe { pm_arena_new() };
+    let mut parser: *mut pm_parser_t = std::ptr::null_mut();
+    let node = unsafe { NonNull::new_unchecked(pm_parse_stream(&raw mut parser, arena, raw.as_ptr(), options.options)) };
+    let result = unsafe { ParseResult::new(source.as_bytes(), arena, parser, node) };
+
+    if let Some(error) = stream.error {
+        return Err(error);
+    }
+
+    Ok(OwnedParseResult { result, options, _source: source })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read};
+
+    use super::{parse_file, parse_many, parse_stream, OwnedParseResult, SourceError};
+    use crate::{Location, Node, Options, ParseOptions, ParseResult};
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<ParseOptions>();
+        assert_send_sync::<ParseResult<'_>>();
+        assert_send_sync::<OwnedParseResult>();
+        assert_send_sync::<Node<'_>>();
+        assert_send_sync::<Location<'_>>();
+        assert_send_sync::<crate::CallNode<'_>>();
+    }
+
+    #[test]
+    fn test_owned_parse_result_across_threads() {
+        let owned = OwnedParseResult::new(b"foo(bar)".to_vec(), Options::default().build());
+        let owned = std::thread::spawn(move || {
+            assert!(owned.result().is_success());
+            owned
+        })
+        .join()
+        .unwrap();
+
+        let node = owned.node();
+        std::thread::scope(|scope| {
+            scope.spawn(|| assert_eq!(node.location().as_slice(), b"foo(bar)"));
+        });
+    }
+
+    #[test]
+    fn test_parse_many() {
+        let directory = std::env::temp_dir().join(format!("ruby-prism-parse-many-{}", std::process::id()));
+        std::fs::create_dir_all(&directory).unwrap();
+
+        let mut paths: Vec<std::path::PathBuf> = (0..16)
+            .map(|index| {
+                let path = directory.join(format!("{index}.rb"));
+                std::fs::write(&path, format!("foo_{index}\n")).unwrap();
+                path
+            })
+            .collect();
+        paths.insert(3, directory.join("missing.rb"));
+
+        let results = parse_many(&paths, &Options::default());
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(results.len(), paths.len());
+        assert!(matches!(results[3], Err(SourceError::Io(_))));
+
+        for (path, result) in paths.iter().zip(&results).filter(|(_, result)| result.is_ok()) {
+            let owned = result.as_ref().unwrap();
+            let index = path.file_stem().unwrap().to_str().unwrap();
+
+            assert_eq!(owned.source(), format!("foo_{index}\n").as_bytes());
+            assert_eq!(owned.result().filepath(), path.to_string_lossy().as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_parse_many_shared_filepath() {
+        let path = std::env::temp_dir().join(format!("ruby-prism-parse-many-shared-{}.rb", std::process::id()));
+        std::fs::write(&path, "foo").unwrap();
+
+        let results = parse_many(&[&path, &path], &Options::default().filepath("shared.rb"));
+        std::fs::remove_file(&path).unwrap();
+
+        for result in results {
+            assert_eq!(result.unwrap().result().filepath(), b"shared.rb");
+        }
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let path = std::env::temp_dir().join(format!("ruby-prism-parse-file-{}.rb", std::process::id()));
+        std::fs::write(&path, "foo = 1\nfoo +\n").unwrap();
+
+        let options = Options::default().filepath("test.rb").build();
+        let owned = parse_file(&path, &options).unwrap();
+        drop(options);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(owned.source(), b"foo = 1\nfoo +\n");
+        assert_eq!(owned.result().filepath(), b"test.rb");
+        assert!(owned.result().is_failure());
+        assert_eq!(owned.node().as_program_node().unwrap().statements().body().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_file_empty() {
+        let path = std::env::temp_dir().join(format!("ruby-prism-parse-file-empty-{}.rb", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+
+        let owned = parse_file(&path, &Options::default().build()).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(owned.source().is_empty());
+        assert!(owned.result().is_success());
+    }
+
+    #[test]
+    fn test_parse_file_directory() {
+        let error = parse_file(std::env::temp_dir(), &Options::default().build()).unwrap_err();
+        assert!(matches!(error, SourceError::Directory));
+    }
+
+    #[test]
+    fn test_parse_file_missing() {
+        let error = parse_file("/this/path/does/not/exist.rb", &Options::default().build()).unwrap_err();
+        match error {
+            SourceError::Io(error) => assert_eq!(error.kind(), std::io::ErrorKind::NotFound),
+            error => panic!("Unexpected error: {error}"),
+        }
+    }
+
+    #[test]
+    fn test_owned_parse_result() {
+        fn parse_owned(source: &str) -> OwnedParseResult {
+            OwnedParseResult::new(source.to_string().into_bytes(), Options::default().filepath("owned.rb").build())
+        }
+
+        let results: Vec<OwnedParseResult> = ["foo", "bar + baz", "<>"].into_iter().map(parse_owned).collect();
+
+        assert_eq!(results[0].source(), b"foo");
+        assert_eq!(results[0].result().filepath(), b"owned.rb");
+        assert!(results[1].result().is_success());
+        assert!(results[2].result().is_failure());
+
+        let node = results[1].node();
+        let call = node.as_program_node().unwrap().statements().body().iter().next().unwrap();
+        assert_eq!(call.location().as_slice(), b"bar + baz");
+    }
+
+    #[test]
+    fn test_owned_parse_result_empty() {
+        let owned = OwnedParseResult::new(Vec::new(), Options::default().build());
+        assert!(owned.source().is_empty());
+        assert!(owned.result().is_success());
+    }
+
+    #[test]
+    fn test_parse_stream() {
+        let mut reader: &[u8] = b"foo = 1\nbar\n";
+        let owned = parse_stream(&mut reader, &Options::default().build()).unwrap();
+
+        assert_eq!(owned.source(), b"foo = 1\nbar\n");
+        assert!(owned.result().is_success());
+        assert_eq!(owned.node().as_program_node().unwrap().statements().body().len(), 2);
+    }
+
+    #[test]
+    fn test_parse_stream_without_trailing_newline() {
+        let owned = parse_stream(&b"foo\nbar"[..], &Options::default().build()).unwrap();
+        assert_eq!(owned.source(), b"foo\nbar");
+    }
+
+    #[test]
+    fn test_parse_stream_long_line() {
+        let source = format!("foo = \"{}\"\n", "a".repeat(10_000));
+        let owned = parse_stream(BufReader::with_capacity(16, source.as_bytes()), &Options::default().build()).unwrap();
+
+        assert_eq!(owned.source(), source.as_bytes());
+        assert!(owned.result().is_success());
+    }
+
+    #[test]
+    fn test_parse_stream_stops_at_end() {
+        let mut reader: &[u8] = b"foo\n__END__\nbar\nbaz\n";
+        let owned = parse_stream(&mut reader, &Options::default().build()).unwrap();
+
+        assert_eq!(owned.source(), b"foo\n__END__\n");
+        assert!(owned.result().data_loc().is_some());
+
+        let mut rest = String::new();
+        reader.read_to_string(&mut rest).unwrap();
+        assert_eq!(rest, "bar\nbaz\n");
+    }
+
+    #[test]
+    fn test_parse_stream_end_in_heredoc() {
+        let source = "<<~EOS\n__END__\nEOS\n";
+        let owned = parse_stream(source.as_bytes(), &Options::default().build()).unwrap();
+
+        assert_eq!(owned.source(), source.as_bytes());
+        assert!(owned.result().is_success());
+    }
+
+    #[test]
+    fn test_parse_stream_error() {
+        struct Failing;
+
+        impl Read for Failing {
+            fn read(&mut self, _buf: &mut [u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::other("failing"))
+            }
+        }
+
+        impl BufRead for Failing {
+            fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+                Err(std::io::Error::other("failing"))
+            }
+
+            fn consume(&mut self, _amt: usize) {}
+        }
+
+        let error = parse_stream(Failing, &Options::default().build()).unwrap_err();
+        assert_eq!(error.to_string(), "failing");
+    }
+}
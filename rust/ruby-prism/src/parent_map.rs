@@ -0,0 +1,194 @@
+//! Parent lookups for nodes in a syntax tree.
+//!
+//! Nodes only point at their children, so this module walks a tree once and
+//! records the parent of every node in it, keyed by the address of the node in
+//! the arena. That address is stable for as long as the parse result lives.
+
+use std::collections::HashMap;
+use std::marker::PhantomData;
+
+use ruby_prism_sys::{pm_node_t, pm_parser_t};
+
+use crate::Node;
+
+/// A map from every node in a tree to its parent, built once from a root node
+/// so that ancestors can be looked up without walking the tree again.
+#[derive(Debug)]
+pub struct ParentMap<'pr> {
+    parser: *const pm_parser_t,
+    root: *mut pm_node_t,
+    parents: HashMap<*mut pm_node_t, *mut pm_node_t>,
+    marker: PhantomData<&'pr pm_node_t>,
+}
+
+impl<'pr> ParentMap<'pr> {
+    /// Walks the tree under the given root node and records the parent of
+    /// every node in it.
+    #[must_use]
+    pub fn new(root: &Node<'pr>) -> Self {
+        let mut parents = HashMap::new();
+        let mut stack = vec![Node::new(root.parser(), root.as_ptr())];
+
+        while let Some(node) = stack.pop() {
+            let parent = node.as_ptr();
+            node.each_child(|child| {
+                parents.insert(child.as_ptr(), parent);
+                stack.push(child);
+            });
+        }
+
+        ParentMap {
+            parser: root.parser(),
+            root: root.as_ptr(),
+            parents,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the root node that the map was built from.
+    #[must_use]
+    pub fn root(&self) -> Node<'pr> {
+        Node::new(self.parser, self.root)
+    }
+
+    /// Returns the number of nodes in the map, including the root.
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.parents.len() + 1
+    }
+
+    /// Returns false, since the map always contains at least its root node.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        false
+    }
+
+    /// Returns true if the given node is the root or one of its descendants.
+    #[must_use]
+    pub fn contains(&self, node: &Node<'pr>) -> bool {
+        node.as_ptr() == self.root || self.parents.contains_key(&node.as_ptr())
+    }
+
+    /// Returns the parent of the given node, or `None` if it is the root or is
+    /// not part of the tree.
+    #[must_use]
+    pub fn parent(&self, node: &Node<'pr>) -> Option<Node<'pr>> {
+        self.parents.get(&node.as_ptr()).map(|&parent| Node::new(self.parser, parent))
+    }
+
+    /// Returns an iterator over the ancestors of the given node, starting with
+    /// its parent and ending with the root.
+    #[must_use]
+    pub const fn ancestors(&self, node: &Node<'pr>) -> Ancestors<'_, 'pr> {
+        Ancestors { map: self, current: node.as_ptr() }
+    }
+
+    /// Returns the closest ancestor of the given node that is of type `T`,
+    /// such as the `DefNode` or `ClassNode` that encloses it.
+    #[must_use]
+    pub fn enclosing<T: TryFrom<Node<'pr>>>(&self, node: &Node<'pr>) -> Option<T> {
+        self.ancestors(node).find_map(|ancestor| T::try_from(ancestor).ok())
+    }
+}
+
+/// An iterator over the ancestors of a node, from its parent up to the root.
+pub struct Ancestors<'a, 'pr> {
+    map: &'a ParentMap<'pr>,
+    current: *mut pm_node_t,
+}
+
+impl<'pr> Iterator for Ancestors<'_, 'pr> {
+    type Item = Node<'pr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let parent = *self.map.parents.get(&self.current)?;
+        self.current = parent;
+        Some(Node::new(self.map.parser, parent))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParentMap;
+    use crate::{parse, ClassNode, DefNode, ModuleNode, Node, TryVisit};
+
+    /// Returns the first call node with the given name.
+    fn find_call<'pr>(root: &Node<'pr>, name: &[u8]) -> Node<'pr> {
+        use std::ops::ControlFlow;
+
+        use crate::{CallNode, Skip};
+
+        struct Finder<'a>(&'a [u8]);
+
+        impl<'pr> TryVisit<'pr> for Finder<'_> {
+            type Break = Node<'pr>;
+
+            fn visit_call_node(&mut self, node: &CallNode<'pr>) -> ControlFlow<Self::Break, Skip> {
+                if node.name().as_slice() == self.0 {
+                    ControlFlow::Break(node.as_node())
+                } else {
+                    ControlFlow::Continue(Skip::Nothing)
+                }
+            }
+        }
+
+        match Finder(name).visit(root) {
+            ControlFlow::Break(node) => node,
+            ControlFlow::Continue(()) => panic!("Expected to find a call"),
+        }
+    }
+
+    #[test]
+    fn test_parent() {
+        let result = parse(b"foo(bar)");
+        let root = result.node();
+        let map = ParentMap::new(&root);
+
+        let bar = find_call(&root, b"bar");
+        let arguments = map.parent(&bar).unwrap();
+        assert!(arguments.as_arguments_node().is_some());
+
+        let foo = map.parent(&arguments).unwrap();
+        assert_eq!(foo.location().as_slice(), b"foo(bar)");
+
+        assert!(map.parent(&root).is_none());
+        assert!(map.contains(&bar));
+        assert_eq!(map.len(), 5);
+    }
+
+    #[test]
+    fn test_ancestors() {
+        let result = parse(b"module Foo\n  class Bar\n    def baz\n      qux\n    end\n  end\nend\n");
+        let root = result.node();
+        let map = ParentMap::new(&root);
+
+        let qux = find_call(&root, b"qux");
+        let names: Vec<&str> = map
+            .ancestors(&qux)
+            .map(|node| match node {
+                Node::ProgramNode { .. } => "program",
+                Node::StatementsNode { .. } => "statements",
+                Node::ModuleNode { .. } => "module",
+                Node::ClassNode { .. } => "class",
+                Node::DefNode { .. } => "def",
+                _ => "other",
+            })
+            .collect();
+
+        assert_eq!(names, ["statements", "def", "statements", "class", "statements", "module", "statements", "program"]);
+        assert_eq!(map.ancestors(&qux).last().unwrap().location().start(), map.root().location().start());
+    }
+
+    #[test]
+    fn test_enclosing() {
+        let result = parse(b"module Foo\n  class Bar\n    def baz\n      qux\n    end\n  end\nend\n");
+        let root = result.node();
+        let map = ParentMap::new(&root);
+        let qux = find_call(&root, b"qux");
+
+        assert_eq!(map.enclosing::<DefNode<'_>>(&qux).unwrap().name().as_slice(), b"baz");
+        assert_eq!(map.enclosing::<ClassNode<'_>>(&qux).unwrap().name().as_slice(), b"Bar");
+        assert_eq!(map.enclosing::<ModuleNode<'_>>(&qux).unwrap().name().as_slice(), b"Foo");
+        assert!(map.enclosing::<DefNode<'_>>(&root).is_none());
+    }
+}
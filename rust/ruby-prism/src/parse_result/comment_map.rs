@@ -0,0 +1,267 @@
+//! Attaching comments to the nodes and locations that they describe.
+//!
+//! This follows `Prism::ParseResult::Comments` in the Ruby API. For each
+//! comment, it finds the nearest targets before and after it among the children
+//! and location fields of the innermost node that encloses it. A trailing
+//! comment (one on the same line as other source code) prefers the target
+//! before it, while a comment on its own line prefers the target after it.
+
+use std::collections::HashMap;
+
+use ruby_prism_sys::{pm_node_t, pm_parser_t};
+
+use super::{Comment, CommentType, Location, ParseResult};
+use crate::{FieldValue, Node, NodeId};
+
+/// Something in the tree that comments can be attached to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum Target {
+    /// A node, identified by its id.
+    Node(NodeId),
+
+    /// A location field on a node, such as the `end` keyword of a class,
+    /// identified by its start offset and length.
+    Location(u32, u32),
+}
+
+/// A target along with the range of the source that it covers, and the node
+/// to descend into if the target is a node.
+struct Candidate {
+    target: Target,
+    node: Option<*mut pm_node_t>,
+    start: u32,
+    end: u32,
+}
+
+impl Candidate {
+    fn node(node: &Node<'_>) -> Self {
+        let location = node.location();
+        Self {
+            target: Target::Node(node.id()),
+            node: Some(node.as_ptr()),
+            start: location.start(),
+            end: location.end(),
+        }
+    }
+
+    const fn location(location: &Location<'_>) -> Self {
+        Self {
+            target: Target::Location(location.start, location.length),
+            node: None,
+            start: location.start(),
+            end: location.end(),
+        }
+    }
+}
+
+/// The comments in a parse result, attached to the nodes and locations they
+/// describe. Created by [`ParseResult::attach_comments`].
+///
+/// Nodes and locations from a different parse result have no comments
+/// attached, even if a node there has the same id as one in this result.
+#[derive(Debug)]
+pub struct CommentMap<'pr> {
+    parser: *const pm_parser_t,
+    leading: HashMap<Target, Vec<Comment<'pr>>>,
+    trailing: HashMap<Target, Vec<Comment<'pr>>>,
+}
+
+impl<'pr> CommentMap<'pr> {
+    /// Returns the comments attached to the given target, checking that it
+    /// belongs to the parse result that the map was built from.
+    fn get<'a>(&self, comments: &'a HashMap<Target, Vec<Comment<'pr>>>, parser: *const pm_parser_t, target: Target) -> &'a [Comment<'pr>] {
+        if parser != self.parser {
+            return &[];
+        }
+
+        comments.get(&target).map_or(&[], Vec::as_slice)
+    }
+
+    /// Returns the comments attached before the given node, in source order.
+    #[must_use]
+    pub fn leading_comments(&self, node: &Node<'_>) -> &[Comment<'pr>] {
+        self.get(&self.leading, node.parser(), Target::Node(node.id()))
+    }
+
+    /// Returns the comments attached after the given node, in source order.
+    #[must_use]
+    pub fn trailing_comments(&self, node: &Node<'_>) -> &[Comment<'pr>] {
+        self.get(&self.trailing, node.parser(), Target::Node(node.id()))
+    }
+
+    /// Returns the comments attached before the given location field, such as
+    /// a comment on the line before the `end` keyword of a class.
+    #[must_use]
+    pub fn leading_comments_for_location(&self, location: &Location<'_>) -> &[Comment<'pr>] {
+        self.get(&self.leading, location.parser, Target::Location(location.start, location.length))
+    }
+
+    /// Returns the comments attached after the given location field.
+    #[must_use]
+    pub fn trailing_comments_for_location(&self, location: &Location<'_>) -> &[Comment<'pr>] {
+        self.get(&self.trailing, location.parser, Target::Location(location.start, location.length))
+    }
+}
+
+/// Adds the given node to the candidates, or the nodes in its body if it is a
+/// statements node.
+fn push_node(candidates: &mut Vec<Candidate>, node: &Node<'_>) {
+    if let Some(statements) = node.as_statements_node() {
+        candidates.extend(statements.body().iter().map(|child| Candidate::node(&child)));
+    } else {
+        candidates.push(Candidate::node(node));
+    }
+}
+
+/// Finds the nearest targets before and after the given range of the source,
+/// along with the innermost node that encloses it.
+fn nearest_targets(mut node: Node<'_>, start: u32, end: u32) -> (Option<Target>, Target, Option<Target>) {
+    'search: loop {
+        let mut candidates = Vec::new();
+        for (_, value) in node.fields() {
+            match value {
+                FieldValue::Node(child) | FieldValue::OptionalNode(Some(child)) => push_node(&mut candidates, &child),
+                FieldValue::NodeList(list) => list.iter().for_each(|child| push_node(&mut candidates, &child)),
+                FieldValue::Location(location) | FieldValue::OptionalLocation(Some(location)) => candidates.push(Candidate::location(&location)),
+                _ => {},
+            }
+        }
+        candidates.sort_by_key(|candidate| candidate.start);
+
+        let mut preceding = None;
+        let mut following = None;
+        let mut left = 0;
+        let mut right = candidates.len();
+
+        // A binary search for the nearest targets, which descends into any
+        // node that completely encloses the comment.
+        while left < right {
+            let middle = left.midpoint(right);
+            let candidate = &candidates[middle];
+
+            if let Some(pointer) = candidate.node {
+                if candidate.start <= start && end <= candidate.end {
+                    node = Node::new(node.parser(), pointer);
+                    continue 'search;
+                }
+            }
+
+            if candidate.end <= start {
+                preceding = Some(candidate.target);
+                left = middle + 1;
+            } else if end <= candidate.start {
+                following = Some(candidate.target);
+                right = middle;
+            } else {
+                // Prism never produces a target that overlaps a comment, but
+                // settle for what we have found so far if it does.
+                break;
+            }
+        }
+
+        return (preceding, Target::Node(node.id()), following);
+    }
+}
+
+/// Returns true if the given comment follows other source code on the same
+/// line.
+fn is_trailing(comment: &Comment<'_>) -> bool {
+    comment.type_() != CommentType::EmbDocComment && !comment.location().start_line_slice().iter().all(|&byte| matches!(byte, b'\0' | b'\t' | b'\n' | 0x0b | 0x0c | b'\r' | b' '))
+}
+
+impl ParseResult<'_> {
+    /// Attaches each comment in the source to the nearest node or location
+    /// field, as either a leading or a trailing comment.
+    #[must_use]
+    pub fn attach_comments(&self) -> CommentMap<'_> {
+        let mut map = CommentMap {
+            parser: self.parser,
+            leading: HashMap::new(),
+            trailing: HashMap::new(),
+        };
+
+        for comment in self.comments() {
+            let location = comment.location();
+            let (preceding, enclosing, following) = nearest_targets(self.node(), location.start(), location.end());
+
+            let (target, leading) = if is_trailing(&comment) {
+                preceding.map_or_else(|| (following.unwrap_or(enclosing), true), |preceding| (preceding, false))
+            } else if let Some(following) = following {
+                (following, true)
+            } else {
+                preceding.map_or((enclosing, true), |preceding| (preceding, false))
+            };
+
+            let comments = if leading { &mut map.leading } else { &mut map.trailing };
+            comments.entry(target).or_default().push(comment);
+        }
+
+        map
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parse;
+
+    fn texts<'a>(comments: &'a [crate::Comment<'_>]) -> Vec<&'a [u8]> {
+        comments.iter().map(crate::Comment::text).collect()
+    }
+
+    #[test]
+    fn test_leading_and_trailing() {
+        let result = parse(b"# leading\nfoo # trailing\nbar\n");
+        let map = result.attach_comments();
+        let body = result.node().as_program_node().unwrap().statements().body();
+
+        let foo = body.first().unwrap();
+        assert_eq!(texts(map.leading_comments(&foo)), [b"# leading"]);
+        assert_eq!(texts(map.trailing_comments(&foo)), [b"# trailing"]);
+
+        let bar = body.last().unwrap();
+        assert!(map.leading_comments(&bar).is_empty());
+        assert!(map.trailing_comments(&bar).is_empty());
+    }
+
+    #[test]
+    fn test_own_line_after_last_statement() {
+        let result = parse(b"foo\n# after\n");
+        let map = result.attach_comments();
+        let foo = result.node().as_program_node().unwrap().statements().body().first().unwrap();
+
+        assert_eq!(texts(map.trailing_comments(&foo)), [b"# after"]);
+    }
+
+    #[test]
+    fn test_location_field() {
+        let result = parse(b"class Foo\n  bar\n  # last\nend\n");
+        let map = result.attach_comments();
+        let class = result.node().as_program_node().unwrap().statements().body().first().unwrap();
+        let class = class.as_class_node().unwrap();
+
+        assert_eq!(texts(map.leading_comments_for_location(&class.end_keyword_loc())), [b"# last"]);
+        assert!(map.leading_comments(&class.as_node()).is_empty());
+    }
+
+    #[test]
+    fn test_foreign_node() {
+        let result = parse(b"# leading\nfoo\n");
+        let other = parse(b"# leading\nfoo\n");
+        let map = result.attach_comments();
+
+        let foo = result.node().as_program_node().unwrap().statements().body().first().unwrap();
+        let other_foo = other.node().as_program_node().unwrap().statements().body().first().unwrap();
+        assert_eq!(foo.id(), other_foo.id());
+        assert_eq!(texts(map.leading_comments(&foo)), [b"# leading"]);
+        assert!(map.leading_comments(&other_foo).is_empty());
+        assert!(map.leading_comments_for_location(&other_foo.location()).is_empty());
+    }
+
+    #[test]
+    fn test_enclosing() {
+        let result = parse(b"# only a comment\n");
+        let map = result.attach_comments();
+
+        assert_eq!(texts(map.leading_comments(&result.node())), [b"# only a comment"]);
+    }
+}
@@ -3,15 +3,44 @@
 use std::ffi::CStr;
 use std::marker::PhantomData;
 
-use ruby_prism_sys::{pm_diagnostic_location, pm_diagnostic_message, pm_diagnostic_t, pm_parser_t};
+use ruby_prism_sys::{pm_diagnostic_error_level, pm_diagnostic_id, pm_diagnostic_location, pm_diagnostic_message, pm_diagnostic_t, pm_diagnostic_type, pm_diagnostic_warning_level, pm_error_level_t, pm_parser_t, pm_warning_level_t};
 
 use super::Location;
+use crate::DiagnosticKind;
+
+/// The level of an error diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorLevel {
+    /// For errors that should raise a `SyntaxError`.
+    Syntax,
+    /// For errors that should raise an `ArgumentError`.
+    Argument,
+    /// For errors that should raise a `LoadError`.
+    Load,
+}
+
+/// The level of a warning diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum WarningLevel {
+    /// For warnings which should be emitted if `$VERBOSE` is not `nil`.
+    Default,
+    /// For warnings which should be emitted if `$VERBOSE` is `true`.
+    Verbose,
+}
+
+/// Whether a set of diagnostics holds errors or warnings.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum DiagnosticSeverity {
+    Error,
+    Warning,
+}
 
 /// A diagnostic message that came back from the parser.
 #[derive(Debug)]
 pub struct Diagnostic<'pr> {
     raw: *const pm_diagnostic_t,
     parser: *const pm_parser_t,
+    severity: DiagnosticSeverity,
     marker: PhantomData<&'pr pm_diagnostic_t>,
 }
 
@@ -40,6 +69,59 @@ impl<'pr> Diagnostic<'pr> {
             marker: PhantomData,
         }
     }
+
+    /// Returns the kind of the diagnostic, which can be used to filter or
+    /// suppress diagnostics by their ID. Returns `None` if prism reports a
+    /// diagnostic that is newer than these bindings.
+    #[must_use]
+    pub fn kind(&self) -> Option<DiagnosticKind> {
+        DiagnosticKind::from_id(unsafe { pm_diagnostic_id(self.raw) })
+    }
+
+    /// Returns the name of the diagnostic as prism reports it, which is the
+    /// same as [`DiagnosticKind::name`] for every kind these bindings know.
+    pub(super) fn type_name(&self) -> &str {
+        unsafe { CStr::from_ptr(pm_diagnostic_type(self.raw)) }.to_str().unwrap_or_default()
+    }
+
+    /// Returns the level of the diagnostic if it is an error, or `None` if it
+    /// is a warning.
+    ///
+    /// # Panics
+    ///
+    /// Panics if prism reports an unknown error level.
+    #[must_use]
+    pub fn error_level(&self) -> Option<ErrorLevel> {
+        if self.severity != DiagnosticSeverity::Error {
+            return None;
+        }
+
+        match unsafe { pm_diagnostic_error_level(self.raw) } {
+            pm_error_level_t::PM_ERROR_LEVEL_SYNTAX => Some(ErrorLevel::Syntax),
+            pm_error_level_t::PM_ERROR_LEVEL_ARGUMENT => Some(ErrorLevel::Argument),
+            pm_error_level_t::PM_ERROR_LEVEL_LOAD => Some(ErrorLevel::Load),
+            level => panic!("Unknown error level: {level:?}"),
+        }
+    }
+
+    /// Returns the level of the diagnostic if it is a warning, or `None` if it
+    /// is an error.
+    ///
+    /// # Panics
+    ///
+    /// Panics if prism reports an unknown warning level.
+    #[must_use]
+    pub fn warning_level(&self) -> Option<WarningLevel> {
+        if self.severity != DiagnosticSeverity::Warning {
+            return None;
+        }
+
+        match unsafe { pm_diagnostic_warning_level(self.raw) } {
+            pm_warning_level_t::PM_WARNING_LEVEL_DEFAULT => Some(WarningLevel::Default),
+            pm_warning_level_t::PM_WARNING_LEVEL_VERBOSE => Some(WarningLevel::Verbose),
+            level => panic!("Unknown warning level: {level:?}"),
+        }
+    }
 }
 
 /// An iterator over diagnostics collected from the parse result.
@@ -47,12 +129,13 @@ pub struct Diagnostics<'pr> {
     ptrs: Vec<*const pm_diagnostic_t>,
     index: usize,
     parser: *const pm_parser_t,
+    severity: DiagnosticSeverity,
     marker: PhantomData<&'pr pm_diagnostic_t>,
 }
 
 impl Diagnostics<'_> {
-    pub(crate) const fn new(ptrs: Vec<*const pm_diagnostic_t>, parser: *const pm_parser_t) -> Self {
-        Diagnostics { ptrs, index: 0, parser, marker: PhantomData }
+    pub(super) const fn new(ptrs: Vec<*const pm_diagnostic_t>, parser: *const pm_parser_t, severity: DiagnosticSeverity) -> Self {
+        Diagnostics { ptrs, index: 0, parser, severity, marker: PhantomData }
     }
 }
 
@@ -66,6 +149,7 @@ impl<'pr> Iterator for Diagnostics<'pr> {
             Some(Diagnostic {
                 raw: diagnostic,
                 parser: self.parser,
+                severity: self.severity,
                 marker: PhantomData,
             })
         } else {
@@ -0,0 +1,258 @@
+//! Converting byte offsets into lines and columns counted in code units.
+//!
+//! This ports `Prism::Source` and `Prism::CodeUnitsCache` from the Ruby API.
+//! Rather than counting code units from the start of the source on every
+//! lookup, the index records each multibyte character once, along with how
+//! many code units it saves compared to its length in bytes. Converting an
+//! offset is then a binary search over those characters.
+
+/// The unit that a column or offset is counted in. Editors generally count in
+/// UTF-16 code units, while other tools count characters (UTF-32).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CodeUnits {
+    /// Bytes, which is the same as the byte offsets used everywhere else.
+    Utf8,
+    /// UTF-16 code units, where characters outside the basic multilingual
+    /// plane take two units.
+    Utf16,
+    /// UTF-32 code units, which is the same as counting characters.
+    Utf32,
+}
+
+/// Returns the index into the given line offsets of the line that contains the
+/// given byte offset.
+pub(super) fn find_line(line_offsets: &[u32], byte_offset: u32) -> usize {
+    line_offsets.partition_point(|&offset| offset <= byte_offset).saturating_sub(1)
+}
+
+/// A character in the source that takes more than one byte. Invalid byte
+/// sequences are counted as a single replacement character, the same way as
+/// Ruby's `String#encode` with `invalid: :replace`.
+#[derive(Debug)]
+pub(super) struct Multibyte {
+    /// The byte offset where the character starts.
+    start: u32,
+
+    /// The byte offset just after the character.
+    end: u32,
+
+    /// The number of UTF-16 code units in the character.
+    utf16: u32,
+
+    /// The number of bytes saved by the characters before this one when
+    /// counting in UTF-16 rather than bytes.
+    utf16_saved: u32,
+
+    /// The number of bytes saved by the characters before this one when
+    /// counting in UTF-32 rather than bytes.
+    utf32_saved: u32,
+}
+
+impl Multibyte {
+    /// Finds the multibyte characters in the given source, in order. Prism
+    /// stores offsets as `u32`, so lengths in the source always fit.
+    #[allow(clippy::cast_possible_truncation)]
+    pub(super) fn scan(source: &[u8]) -> Vec<Self> {
+        let mut multibytes = Vec::new();
+        let mut offset = 0;
+        let mut utf16_saved = 0;
+        let mut utf32_saved = 0;
+
+        let mut push = |start: u32, length: u32, utf16: u32| {
+            multibytes.push(Self {
+                start,
+                end: start + length,
+                utf16,
+                utf16_saved,
+                utf32_saved,
+            });
+            utf16_saved += length - utf16;
+            utf32_saved += length - 1;
+        };
+
+        for chunk in source.utf8_chunks() {
+            for character in chunk.valid().chars() {
+                let length = character.len_utf8() as u32;
+                if length > 1 {
+                    push(offset, length, character.len_utf16() as u32);
+                }
+                offset += length;
+            }
+
+            let invalid = chunk.invalid().len() as u32;
+            if invalid > 0 {
+                push(offset, invalid, 1);
+                offset += invalid;
+            }
+        }
+
+        multibytes
+    }
+}
+
+/// An index over the lines and multibyte characters of a source, for
+/// converting byte offsets into lines and into columns counted in code units.
+/// Created by [`super::ParseResult::line_index`].
+#[derive(Debug, Clone, Copy)]
+pub struct LineIndex<'pr> {
+    source: &'pr [u8],
+    line_offsets: &'pr [u32],
+    start_line: i32,
+    multibytes: &'pr [Multibyte],
+}
+
+impl<'pr> LineIndex<'pr> {
+    pub(super) const fn new(source: &'pr [u8], line_offsets: &'pr [u32], start_line: i32, multibytes: &'pr [Multibyte]) -> Self {
+        LineIndex { source, line_offsets, start_line, multibytes }
+    }
+
+    /// Returns the source that the index was built from.
+    #[must_use]
+    pub const fn source(&self) -> &'pr [u8] {
+        self.source
+    }
+
+    /// Returns the line number that contains the given byte offset. Lines are
+    /// numbered the same way as [`crate::Location::start_line`].
+    #[must_use]
+    pub fn line(&self, byte_offset: u32) -> i32 {
+        self.start_line + i32::try_from(find_line(self.line_offsets, byte_offset)).unwrap_or(i32::MAX)
+    }
+
+    /// Returns the byte offset of the start of the line that contains the
+    /// given byte offset.
+    #[must_use]
+    pub fn line_start(&self, byte_offset: u32) -> u32 {
+        self.line_offsets.get(find_line(self.line_offsets, byte_offset)).copied().unwrap_or(0)
+    }
+
+    /// Returns the byte offset of the end of the line that contains the given
+    /// byte offset, including its newline.
+    #[must_use]
+    #[allow(clippy::cast_possible_truncation)]
+    pub fn line_end(&self, byte_offset: u32) -> u32 {
+        self.line_offsets.get(find_line(self.line_offsets, byte_offset) + 1).copied().unwrap_or(self.source.len() as u32)
+    }
+
+    /// Returns the column in bytes of the given byte offset.
+    #[must_use]
+    pub fn column(&self, byte_offset: u32) -> u32 {
+        byte_offset - self.line_start(byte_offset)
+    }
+
+    /// Returns the number of code units from the start of the source up to
+    /// the given byte offset. A byte offset in the middle of a character
+    /// counts that character as a single code unit.
+    #[must_use]
+    pub fn code_units_offset(&self, byte_offset: u32, units: CodeUnits) -> u32 {
+        if units == CodeUnits::Utf8 {
+            return byte_offset;
+        }
+
+        let index = self.multibytes.partition_point(|multibyte| multibyte.start < byte_offset);
+        let Some(multibyte) = index.checked_sub(1).map(|index| &self.multibytes[index]) else {
+            return byte_offset;
+        };
+
+        let (saved, length) = match units {
+            CodeUnits::Utf16 => (multibyte.utf16_saved, multibyte.utf16),
+            _ => (multibyte.utf32_saved, 1),
+        };
+
+        let start = multibyte.start - saved;
+        if byte_offset >= multibyte.end {
+            start + length + (byte_offset - multibyte.end)
+        } else {
+            start + 1
+        }
+    }
+
+    /// Returns the column of the given byte offset, counted in code units
+    /// from the start of its line.
+    #[must_use]
+    pub fn code_units_column(&self, byte_offset: u32, units: CodeUnits) -> u32 {
+        self.code_units_offset(byte_offset, units) - self.code_units_offset(self.line_start(byte_offset), units)
+    }
+
+    /// Returns the number of characters from the start of the source up to
+    /// the given byte offset.
+    #[must_use]
+    pub fn character_offset(&self, byte_offset: u32) -> u32 {
+        self.code_units_offset(byte_offset, CodeUnits::Utf32)
+    }
+
+    /// Returns the column of the given byte offset, counted in characters
+    /// from the start of its line.
+    #[must_use]
+    pub fn character_column(&self, byte_offset: u32) -> u32 {
+        self.code_units_column(byte_offset, CodeUnits::Utf32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CodeUnits;
+    use crate::{parse, parse_with_options, Options};
+
+    #[test]
+    fn test_ascii() {
+        let result = parse(b"foo\nbar baz\n");
+        let index = result.line_index();
+
+        assert_eq!(index.line(8), 2);
+        assert_eq!(index.line_start(8), 4);
+        assert_eq!(index.line_end(8), 12);
+        assert_eq!(index.column(8), 4);
+        assert_eq!(index.code_units_column(8, CodeUnits::Utf16), 4);
+        assert_eq!(index.character_offset(8), 8);
+    }
+
+    #[test]
+    fn test_multibyte() {
+        // "é" is two bytes and one UTF-16 unit, "😀" is four bytes and two
+        // UTF-16 units.
+        let source = "x = \"é😀\"; y\nz = \"😀\"; w\n";
+        let result = parse(source.as_bytes());
+        let index = result.line_index();
+
+        let y = u32::try_from(source.find('y').unwrap()).unwrap();
+        assert_eq!(index.column(y), 14);
+        assert_eq!(index.code_units_column(y, CodeUnits::Utf8), 14);
+        assert_eq!(index.code_units_column(y, CodeUnits::Utf16), 11);
+        assert_eq!(index.code_units_column(y, CodeUnits::Utf32), 10);
+        assert_eq!(index.character_column(y), 10);
+
+        let w = u32::try_from(source.find('w').unwrap()).unwrap();
+        assert_eq!(index.code_units_offset(w, CodeUnits::Utf16), 23);
+        assert_eq!(index.character_offset(w), 21);
+        assert_eq!(index.code_units_column(w, CodeUnits::Utf16), 10);
+        assert_eq!(index.character_column(w), 9);
+    }
+
+    #[test]
+    fn test_inside_character() {
+        let result = parse("\"😀\"".as_bytes());
+        let index = result.line_index();
+
+        assert_eq!(index.code_units_offset(2, CodeUnits::Utf16), 2);
+        assert_eq!(index.code_units_offset(5, CodeUnits::Utf16), 3);
+        assert_eq!(index.code_units_offset(6, CodeUnits::Utf16), 4);
+        assert_eq!(index.character_offset(5), 2);
+    }
+
+    #[test]
+    fn test_invalid() {
+        let result = parse(b"\"\xe2\x82\" + x");
+        let index = result.line_index();
+
+        assert_eq!(index.character_column(7), 6);
+    }
+
+    #[test]
+    fn test_start_line() {
+        let options = Options::default().line(5).build();
+        let result = parse_with_options(b"foo\nbar\n", &options);
+
+        assert_eq!(result.line_index().line(5), 6);
+    }
+}
@@ -1,20 +1,47 @@
 //! Parse result types for the prism parser.
 
+mod comment_map;
 mod comments;
 mod diagnostics;
+mod line_index;
+mod newlines;
+mod node_find;
+mod render;
+mod rewriter;
+mod tokens;
 
 use std::ptr::NonNull;
+use std::sync::OnceLock;
 
 use ruby_prism_sys::{
-    pm_arena_free, pm_arena_t, pm_comment_t, pm_diagnostic_t, pm_line_offset_list_line_column, pm_location_t, pm_magic_comment_t, pm_node_t, pm_parser_comments_each, pm_parser_comments_size, pm_parser_data_loc, pm_parser_errors_each, pm_parser_errors_size, pm_parser_free,
-    pm_parser_frozen_string_literal, pm_parser_line_offsets, pm_parser_magic_comments_each, pm_parser_magic_comments_size, pm_parser_start, pm_parser_start_line, pm_parser_t, pm_parser_warnings_each, pm_parser_warnings_size,
+    pm_arena_free, pm_arena_t, pm_comment_t, pm_diagnostic_t, pm_line_offset_list_line_column, pm_location_t, pm_magic_comment_t, pm_node_t, pm_parser_comments_each, pm_parser_comments_size, pm_parser_data_loc, pm_parser_end, pm_parser_errors_each, pm_parser_errors_size, pm_parser_filepath,
+    pm_parser_free, pm_parser_frozen_string_literal, pm_parser_line_offsets, pm_parser_magic_comments_each, pm_parser_magic_comments_size, pm_parser_start, pm_parser_start_line, pm_parser_t, pm_parser_warnings_each, pm_parser_warnings_size, pm_string_length, pm_string_source,
 };
 
+pub use self::comment_map::CommentMap;
 pub use self::comments::{Comment, CommentType, Comments, MagicComment, MagicComments};
-pub use self::diagnostics::{Diagnostic, Diagnostics};
-
+pub use self::diagnostics::{Diagnostic, Diagnostics, ErrorLevel, WarningLevel};
+pub use self::line_index::{CodeUnits, LineIndex};
+pub use self::newlines::Newlines;
+pub use self::node_find::NodeAt;
+pub use self::rewriter::{Position, RewriteError, Rewriter, TextEdit};
+pub use self::tokens::{LexResult, Token, Tokens};
+
+use self::diagnostics::DiagnosticSeverity;
+use self::line_index::Multibyte;
 use crate::Node;
 
+/// Returns the offsets of the start of each line in the source of the given
+/// parser.
+///
+/// # Safety
+///
+/// `parser` must point to a parser that outlives the returned slice.
+unsafe fn line_offsets<'a>(parser: *const pm_parser_t) -> &'a [u32] {
+    let list = &*pm_parser_line_offsets(parser);
+    std::slice::from_raw_parts(list.offsets, list.size)
+}
+
 /// A range in the source file, represented as a start offset and length.
 pub struct Location<'pr> {
     pub(crate) parser: *const pm_parser_t,
@@ -83,6 +110,61 @@ impl<'pr> Location<'pr> {
             marker: std::marker::PhantomData,
         }
     }
+
+    /// Returns a new location that extends this one to the end of the first
+    /// occurrence of the given string after it on the same line. Returns
+    /// `None` if the string does not occur on the rest of the line.
+    #[must_use]
+    pub fn adjoin(&self, string: &[u8]) -> Option<Self> {
+        let end = self.end();
+        let suffix = &self.source()[end as usize..self.lines().line_end(end) as usize];
+        let index = (0..=suffix.len().saturating_sub(string.len())).find(|&index| suffix[index..].starts_with(string))?;
+
+        Some(Location {
+            parser: self.parser,
+            start: self.start,
+            length: self.length + u32::try_from(index + string.len()).ok()?,
+            marker: std::marker::PhantomData,
+        })
+    }
+
+    /// Returns an index over the lines of the source that this location is in.
+    /// It has no multibyte characters, so only its lookups in bytes are
+    /// correct.
+    fn lines(&self) -> LineIndex<'pr> {
+        LineIndex::new(self.source(), unsafe { line_offsets(self.parser) }, unsafe { pm_parser_start_line(self.parser) }, &[])
+    }
+
+    /// Returns the entire source that this location is in.
+    fn source(&self) -> &'pr [u8] {
+        unsafe {
+            let start = pm_parser_start(self.parser);
+            let length = pm_parser_end(self.parser).offset_from(start);
+            std::slice::from_raw_parts(start, usize::try_from(length).unwrap_or(0))
+        }
+    }
+
+    /// Returns all of the lines of the source that this location is in,
+    /// including their newlines.
+    pub fn source_lines(&self) -> impl Iterator<Item = &'pr [u8]> {
+        self.source().split_inclusive(|&byte| byte == b'\n')
+    }
+
+    /// Returns the source from the start of the line that this location
+    /// starts on to the end of the line that it ends on, including the
+    /// newline.
+    #[must_use]
+    pub fn slice_lines(&self) -> &'pr [u8] {
+        let lines = self.lines();
+        &self.source()[lines.line_start(self.start) as usize..lines.line_end(self.end()) as usize]
+    }
+
+    /// Returns the content of the line that this location starts on, before
+    /// the start of this location.
+    #[must_use]
+    pub fn start_line_slice(&self) -> &'pr [u8] {
+        &self.source()[self.lines().line_start(self.start) as usize..self.start as usize]
+    }
 }
 
 impl Location<'_> {
@@ -112,6 +194,31 @@ impl Location<'_> {
         self.line_column(self.end()).1
     }
 
+    /// Returns true if this location is empty.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.length == 0
+    }
+
+    /// Returns true if the other location lies entirely within this one.
+    #[must_use]
+    pub const fn contains(&self, other: &Location<'_>) -> bool {
+        self.start <= other.start && other.end() <= self.end()
+    }
+
+    /// Returns true if this location and the other location share at least
+    /// one byte. Empty locations never overlap anything.
+    #[must_use]
+    pub const fn overlaps(&self, other: &Location<'_>) -> bool {
+        self.start < other.end() && other.start < self.end()
+    }
+
+    /// Returns the range of bytes that this location covers in the source.
+    #[must_use]
+    pub const fn as_range(&self) -> std::ops::Range<usize> {
+        self.start as usize..self.end() as usize
+    }
+
     /// Returns the line and column number for the given byte offset.
     fn line_column(&self, cursor: u32) -> (i32, u32) {
         unsafe {
@@ -123,6 +230,41 @@ impl Location<'_> {
     }
 }
 
+// Locations are compared by the range of bytes that they cover, the same way
+// as `Prism::Location#==` in the Ruby API.
+impl PartialEq for Location<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.start == other.start && self.length == other.length
+    }
+}
+
+impl Eq for Location<'_> {}
+
+impl PartialOrd for Location<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Location<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        (self.start, self.length).cmp(&(other.start, other.length))
+    }
+}
+
+impl std::hash::Hash for Location<'_> {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.start.hash(state);
+        self.length.hash(state);
+    }
+}
+
+impl From<Location<'_>> for std::ops::Range<usize> {
+    fn from(location: Location<'_>) -> Self {
+        location.as_range()
+    }
+}
+
 impl std::fmt::Debug for Location<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let slice: &[u8] = self.as_slice();
@@ -165,11 +307,18 @@ pub struct ParseResult<'pr> {
     arena: *mut pm_arena_t,
     parser: *mut pm_parser_t,
     node: NonNull<pm_node_t>,
+    multibytes: OnceLock<Vec<Multibyte>>,
 }
 
 impl<'pr> ParseResult<'pr> {
     pub(crate) const unsafe fn new(source: &'pr [u8], arena: *mut pm_arena_t, parser: *mut pm_parser_t, node: NonNull<pm_node_t>) -> Self {
-        ParseResult { source, arena, parser, node }
+        ParseResult {
+            source,
+            arena,
+            parser,
+            node,
+            multibytes: OnceLock::new(),
+        }
     }
 
     /// Returns the source string that was parsed.
@@ -178,6 +327,21 @@ impl<'pr> ParseResult<'pr> {
         self.source
     }
 
+    /// Returns the file path that was given in the parse options, or an empty
+    /// slice if none was given.
+    #[must_use]
+    pub fn filepath(&self) -> &'pr [u8] {
+        unsafe {
+            let filepath = pm_parser_filepath(self.parser);
+            let length = pm_string_length(filepath);
+            if length == 0 {
+                &[]
+            } else {
+                std::slice::from_raw_parts(pm_string_source(filepath), length)
+            }
+        }
+    }
+
     /// Returns whether we found a `frozen_string_literal` magic comment with a true value.
     #[must_use]
     pub fn frozen_string_literals(&self) -> bool {
@@ -197,12 +361,58 @@ impl<'pr> ParseResult<'pr> {
     /// that was parsed.
     #[must_use]
     pub fn line_offsets(&self) -> &'pr [u32] {
-        unsafe {
-            let list = &*pm_parser_line_offsets(self.parser);
-            std::slice::from_raw_parts(list.offsets, list.size)
+        unsafe { line_offsets(self.parser) }
+    }
+
+    /// Returns the byte offset of the given line and byte column. Lines are
+    /// numbered the same way as [`Location::start_line`], starting from the
+    /// line given in the parse options, and columns start at 0. Returns `None`
+    /// if the line is outside of the source or the column is past the end of
+    /// the line. A column may point at the newline that ends its line, but
+    /// only the last line has a position after its final byte, which is the
+    /// end of the source.
+    #[must_use]
+    pub fn byte_offset(&self, line: i32, column: u32) -> Option<u32> {
+        let start_line = unsafe { pm_parser_start_line(self.parser) };
+        let index = usize::try_from(line.checked_sub(start_line)?).ok()?;
+
+        let offsets = self.line_offsets();
+        let line_start = *offsets.get(index)?;
+        let offset = line_start.checked_add(column)?;
+
+        match offsets.get(index + 1) {
+            Some(&line_end) => (offset < line_end).then_some(offset),
+            None => (offset as usize <= self.source.len()).then_some(offset),
         }
     }
 
+    /// Returns a location covering the bytes from the start offset up to but
+    /// not including the end offset. Returns `None` if the start is after the
+    /// end or the end is past the end of the source.
+    #[must_use]
+    pub const fn location_from_range(&self, start: u32, end: u32) -> Option<Location<'_>> {
+        if start > end || end as usize > self.source.len() {
+            return None;
+        }
+
+        Some(Location {
+            parser: self.parser,
+            start,
+            length: end - start,
+            marker: std::marker::PhantomData,
+        })
+    }
+
+    /// Returns an index for converting byte offsets into lines and columns
+    /// counted in code units. The multibyte characters in the source are found
+    /// the first time this is called and cached for the lifetime of the parse
+    /// result.
+    #[must_use]
+    pub fn line_index(&self) -> LineIndex<'_> {
+        let multibytes = self.multibytes.get_or_init(|| Multibyte::scan(self.source));
+        LineIndex::new(self.source, self.line_offsets(), unsafe { pm_parser_start_line(self.parser) }, multibytes)
+    }
+
     /// Returns an iterator that can be used to iterate over the errors in the
     /// parse result.
     #[must_use]
@@ -212,7 +422,7 @@ impl<'pr> ParseResult<'pr> {
         unsafe {
             pm_parser_errors_each(self.parser, Some(collect_diagnostic), (&raw mut ptrs).cast());
         }
-        Diagnostics::new(ptrs, self.parser)
+        Diagnostics::new(ptrs, self.parser, DiagnosticSeverity::Error)
     }
 
     /// Returns an iterator that can be used to iterate over the warnings in the
@@ -224,7 +434,7 @@ impl<'pr> ParseResult<'pr> {
         unsafe {
             pm_parser_warnings_each(self.parser, Some(collect_diagnostic), (&raw mut ptrs).cast());
         }
-        Diagnostics::new(ptrs, self.parser)
+        Diagnostics::new(ptrs, self.parser, DiagnosticSeverity::Warning)
     }
 
     /// Returns an iterator that can be used to iterate over the comments in the
@@ -309,4 +519,118 @@ mod tests {
         assert!(result.is_failure());
         assert!(!result.is_success());
     }
+
+    #[test]
+    fn test_byte_offset() {
+        use crate::{parse_with_options, Options};
+
+        let result = parse(b"foo\nbar baz\n");
+        assert_eq!(result.byte_offset(1, 0), Some(0));
+        assert_eq!(result.byte_offset(2, 4), Some(8));
+        assert_eq!(result.byte_offset(2, 7), Some(11));
+        assert_eq!(result.byte_offset(2, 8), None);
+        assert_eq!(result.byte_offset(3, 0), Some(12));
+        assert_eq!(result.byte_offset(3, 1), None);
+        assert_eq!(result.byte_offset(0, 0), None);
+        assert_eq!(result.byte_offset(4, 0), None);
+
+        let options = Options::default().line(10).build();
+        let result = parse_with_options(b"foo\nbar\n", &options);
+        assert_eq!(result.byte_offset(11, 1), Some(5));
+        assert_eq!(result.byte_offset(1, 0), None);
+    }
+
+    #[test]
+    fn test_location_from_range() {
+        let result = parse(b"foo(bar, baz)");
+
+        let start = result.byte_offset(1, 4).unwrap();
+        let end = result.byte_offset(1, 12).unwrap();
+        let location = result.location_from_range(start, end).unwrap();
+        assert_eq!(location.as_slice(), b"bar, baz");
+        assert_eq!(location.start_line(), 1);
+        assert_eq!(location.end_column(), 12);
+
+        assert_eq!(result.byte_offset(1, 13), Some(13));
+        assert!(result.location_from_range(0, 0).unwrap().as_slice().is_empty());
+        assert!(result.location_from_range(5, 4).is_none());
+        assert!(result.location_from_range(0, 14).is_none());
+    }
+
+    #[test]
+    fn test_location_lines() {
+        let result = parse(b"def foo\n  bar(1)\nend\n");
+        let location = result.location_from_range(13, 16).unwrap();
+
+        assert_eq!(location.as_slice(), b"(1)");
+        assert_eq!(location.start_line_slice(), b"  bar");
+        assert_eq!(location.slice_lines(), b"  bar(1)\n");
+        assert_eq!(location.source_lines().collect::<Vec<_>>(), [&b"def foo\n"[..], b"  bar(1)\n", b"end\n"]);
+
+        let location = result.location_from_range(4, 12).unwrap();
+        assert_eq!(location.slice_lines(), b"def foo\n  bar(1)\n");
+    }
+
+    #[test]
+    fn test_location_adjoin() {
+        let result = parse(b"foo(bar) # baz\nqux");
+        let location = result.location_from_range(0, 3).unwrap();
+
+        assert_eq!(location.adjoin(b")").unwrap().as_slice(), b"foo(bar)");
+        assert_eq!(location.adjoin(b"#").unwrap().as_slice(), b"foo(bar) #");
+        assert!(location.adjoin(b"qux").is_none());
+    }
+
+    #[test]
+    fn test_location_comparison() {
+        use std::collections::HashSet;
+        use std::ops::Range;
+
+        let result = parse(b"foo(bar, baz)");
+        let outer = result.location_from_range(4, 12).unwrap();
+        let bar = result.location_from_range(4, 7).unwrap();
+        let baz = result.location_from_range(9, 12).unwrap();
+        let empty = result.location_from_range(7, 7).unwrap();
+
+        assert!(outer.contains(&bar));
+        assert!(!bar.contains(&outer));
+        assert!(outer.overlaps(&baz));
+        assert!(!bar.overlaps(&baz));
+        assert!(!empty.overlaps(&bar));
+        assert!(empty.is_empty());
+
+        assert_eq!(bar, result.location_from_range(4, 7).unwrap());
+        assert_ne!(bar, outer);
+        assert!(bar < outer && outer < baz);
+        assert_eq!([&baz, &bar].into_iter().collect::<HashSet<_>>().len(), 2);
+
+        assert_eq!(bar.as_range(), 4..7);
+        assert_eq!(Range::from(baz), 9..12);
+    }
+
+    #[test]
+    fn test_error_kind_and_level() {
+        use crate::{DiagnosticKind, ErrorLevel};
+
+        let result = parse(b"alias $a $1");
+        let error = result.errors().next().unwrap();
+
+        assert_eq!(error.kind(), Some(DiagnosticKind::AliasArgumentNumberedReference));
+        assert_eq!(error.kind().unwrap().name(), "alias_argument_numbered_reference");
+        assert_eq!(error.error_level(), Some(ErrorLevel::Syntax));
+        assert_eq!(error.warning_level(), None);
+    }
+
+    #[test]
+    fn test_warning_kind_and_level() {
+        use crate::{DiagnosticKind, WarningLevel};
+
+        let result = parse(b"foo +1");
+        let warning = result.warnings().next().unwrap();
+
+        assert_eq!(warning.kind(), Some(DiagnosticKind::AmbiguousFirstArgumentPlus));
+        assert_eq!(warning.warning_level(), Some(WarningLevel::Verbose));
+        assert_eq!(warning.error_level(), None);
+        assert_eq!(DiagnosticKind::from_name("ambiguous_first_argument_plus"), Some(DiagnosticKind::AmbiguousFirstArgumentPlus));
+    }
 }
@@ -0,0 +1,226 @@
+//! Marking the nodes that fire a `line` event.
+//!
+//! This follows `Prism::ParseResult::Newlines` in the Ruby API, which loosely
+//! emulates the `:line` tracepoint event of the Ruby VM. The event fires for
+//! `if` and `unless` statements and for the children of statements lists, and
+//! only for the first such node on each line. Blocks and lambdas track their
+//! lines separately from the code around them.
+
+use std::collections::HashSet;
+use std::marker::PhantomData;
+
+use ruby_prism_sys::{pm_parser_t, PM_NODE_FLAG_NEWLINE};
+
+use super::line_index::find_line;
+use super::ParseResult;
+use crate::{Node, NodeId};
+
+/// The nodes in a parse result that fire a `line` event. Created by
+/// [`ParseResult::mark_newlines`].
+#[derive(Debug)]
+pub struct Newlines<'pr> {
+    parser: *const pm_parser_t,
+    nodes: HashSet<NodeId>,
+    marker: PhantomData<&'pr pm_parser_t>,
+}
+
+impl Newlines<'_> {
+    /// Returns true if the given node fires a `line` event. Nodes from a
+    /// different parse result never do.
+    #[must_use]
+    pub fn contains(&self, node: &Node<'_>) -> bool {
+        node.parser() == self.parser && self.nodes.contains(&node.id())
+    }
+}
+
+/// Walks a tree and collects the nodes that fire a `line` event.
+struct Marker<'pr> {
+    offsets: &'pr [u32],
+    lines: Vec<bool>,
+    marked: Vec<Node<'pr>>,
+}
+
+impl<'pr> Marker<'pr> {
+    /// Visits the given node and its children.
+    fn visit(&mut self, node: &Node<'pr>) {
+        match node {
+            Node::BlockNode { .. } | Node::LambdaNode { .. } => {
+                let outer = std::mem::replace(&mut self.lines, vec![false; self.offsets.len()]);
+                node.each_child(|child| self.visit(&child));
+                self.lines = outer;
+                return;
+            },
+            Node::IfNode { .. } | Node::UnlessNode { .. } => self.mark(node),
+            Node::StatementsNode { .. } => {
+                for child in &node.as_statements_node().unwrap().body() {
+                    self.mark(&child);
+                }
+            },
+            _ => {},
+        }
+
+        node.each_child(|child| self.visit(&child));
+    }
+
+    /// Marks the given node if it is the first on its line, or the node that
+    /// stands in for it.
+    fn mark(&mut self, node: &Node<'pr>) {
+        match node {
+            // These are never marked, their children are marked instead.
+            Node::BeginNode { .. } | Node::ParenthesesNode { .. } => {},
+            Node::IfNode { .. } => self.mark(&node.as_if_node().unwrap().predicate()),
+            Node::UnlessNode { .. } => self.mark(&node.as_unless_node().unwrap().predicate()),
+            Node::UntilNode { .. } => self.mark(&node.as_until_node().unwrap().predicate()),
+            Node::WhileNode { .. } => self.mark(&node.as_while_node().unwrap().predicate()),
+            Node::RescueModifierNode { .. } => self.mark(&node.as_rescue_modifier_node().unwrap().expression()),
+            Node::InterpolatedMatchLastLineNode { .. } => self.mark_first(node.as_interpolated_match_last_line_node().unwrap().parts().first()),
+            Node::InterpolatedRegularExpressionNode { .. } => self.mark_first(node.as_interpolated_regular_expression_node().unwrap().parts().first()),
+            Node::InterpolatedStringNode { .. } => self.mark_first(node.as_interpolated_string_node().unwrap().parts().first()),
+            Node::InterpolatedSymbolNode { .. } => self.mark_first(node.as_interpolated_symbol_node().unwrap().parts().first()),
+            Node::InterpolatedXStringNode { .. } => self.mark_first(node.as_interpolated_x_string_node().unwrap().parts().first()),
+            _ => {
+                let start = node.location().start();
+                let line = find_line(self.offsets, start);
+
+                if !self.lines[line] {
+                    self.lines[line] = true;
+                    self.marked.push(Node::new(node.parser(), node.as_ptr()));
+                }
+            },
+        }
+    }
+
+    /// Marks the first part of an interpolated node, if it has any.
+    fn mark_first(&mut self, first: Option<Node<'pr>>) {
+        if let Some(first) = first {
+            self.mark(&first);
+        }
+    }
+}
+
+impl Node<'_> {
+    /// Returns true if the parser set the newline flag on this node, which it
+    /// does for statements that begin a new line. This is not the same as the
+    /// nodes that fire a `line` event, which [`ParseResult::mark_newlines`]
+    /// finds.
+    #[must_use]
+    pub fn has_newline_flag(&self) -> bool {
+        (unsafe { (*self.as_ptr()).flags } & PM_NODE_FLAG_NEWLINE) != 0
+    }
+}
+
+impl ParseResult<'_> {
+    /// Returns the nodes that fire a `line` event.
+    fn newline_nodes(&self) -> Vec<Node<'_>> {
+        let offsets = self.line_offsets();
+        let mut marker = Marker {
+            offsets,
+            lines: vec![false; offsets.len()],
+            marked: Vec::new(),
+        };
+        marker.visit(&self.node());
+        marker.marked
+    }
+
+    /// Finds the nodes that fire a `line` event, as
+    /// `Prism::ParseResult#mark_newlines!` does in the Ruby API, where
+    /// `Prism::Node#newline?` then answers for the marked nodes. Here
+    /// [`Newlines::contains`] answers instead, and the tree itself is left as
+    /// the parser built it, so [`Node::has_newline_flag`] still reports the
+    /// parser's flag.
+    #[must_use]
+    pub fn mark_newlines(&self) -> Newlines<'_> {
+        Newlines {
+            parser: self.parser,
+            nodes: self.newline_nodes().iter().map(Node::id).collect(),
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the line numbers that fire a `line` event when the code is
+    /// run, in ascending order. Lines are numbered the same way as
+    /// [`crate::Location::start_line`].
+    #[must_use]
+    pub fn executable_lines(&self) -> Vec<i32> {
+        let mut lines: Vec<i32> = self.newline_nodes().iter().map(|node| node.location().start_line()).collect();
+        lines.sort_unstable();
+        lines.dedup();
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Newlines;
+    use crate::{parse, parse_with_options, Node, Options};
+
+    /// Returns the source of every node in the tree that the given predicate
+    /// holds for.
+    fn sources<'pr>(node: &Node<'pr>, predicate: impl Fn(&Node<'pr>) -> bool) -> Vec<&'pr [u8]> {
+        let mut found = Vec::new();
+        let mut stack = vec![Node::new(node.parser(), node.as_ptr())];
+
+        while let Some(node) = stack.pop() {
+            if predicate(&node) {
+                found.push(node.location().as_slice());
+            }
+            node.each_child(|child| stack.push(child));
+        }
+
+        found.sort_unstable();
+        found
+    }
+
+    fn newlines<'pr>(node: &Node<'pr>, newlines: &Newlines<'_>) -> Vec<&'pr [u8]> {
+        sources(node, |node| newlines.contains(node))
+    }
+
+    #[test]
+    fn test_mark_newlines() {
+        let result = parse(b"foo; bar\nif baz\n  qux\nend\n(quux)\n");
+        let marked = result.mark_newlines();
+
+        assert_eq!(newlines(&result.node(), &marked), [&b"baz"[..], b"foo", b"quux", b"qux"]);
+    }
+
+    #[test]
+    fn test_mark_newlines_block() {
+        let result = parse(b"foo { bar }\n");
+        let marked = result.mark_newlines();
+
+        assert_eq!(newlines(&result.node(), &marked), [&b"bar"[..], b"foo { bar }"]);
+    }
+
+    #[test]
+    fn test_mark_newlines_leaves_flags() {
+        let result = parse(b"foo; bar\n(quux)\n");
+        let before = sources(&result.node(), Node::has_newline_flag);
+        let _ = result.mark_newlines();
+
+        assert_eq!(sources(&result.node(), Node::has_newline_flag), before);
+        assert!(before.contains(&&b"bar"[..]));
+    }
+
+    #[test]
+    fn test_mark_newlines_foreign_node() {
+        let result = parse(b"foo\n");
+        let other = parse(b"foo\n");
+        let marked = result.mark_newlines();
+
+        let foo = result.node().as_program_node().unwrap().statements().body().first().unwrap();
+        let other_foo = other.node().as_program_node().unwrap().statements().body().first().unwrap();
+        assert_eq!(foo.id(), other_foo.id());
+        assert!(marked.contains(&foo));
+        assert!(!marked.contains(&other_foo));
+    }
+
+    #[test]
+    fn test_executable_lines() {
+        let result = parse(b"def foo\n  # comment\n  bar\n\n  baz if qux\nend\n");
+        assert_eq!(result.executable_lines(), [1, 3, 5]);
+
+        let options = Options::default().line(10).build();
+        let result = parse_with_options(b"foo\n\nbar\n", &options);
+        assert_eq!(result.executable_lines(), [10, 12]);
+    }
+}
@@ -0,0 +1,142 @@
+//! Finding the node at a given position in the source.
+
+use super::ParseResult;
+use crate::Node;
+
+/// The innermost node that covers a position in the source, along with the
+/// chain of nodes that enclose it.
+#[derive(Debug)]
+pub struct NodeAt<'pr> {
+    node: Node<'pr>,
+    ancestors: Vec<Node<'pr>>,
+}
+
+impl<'pr> NodeAt<'pr> {
+    /// Returns the innermost node that covers the position.
+    #[must_use]
+    pub const fn node(&self) -> &Node<'pr> {
+        &self.node
+    }
+
+    /// Returns the nodes that enclose the innermost node, starting with its
+    /// parent and ending with the root.
+    #[must_use]
+    pub fn ancestors(&self) -> &[Node<'pr>] {
+        &self.ancestors
+    }
+
+    /// Consumes the result and returns the innermost node and its ancestors.
+    #[must_use]
+    pub fn into_parts(self) -> (Node<'pr>, Vec<Node<'pr>>) {
+        (self.node, self.ancestors)
+    }
+}
+
+/// Returns true if the given node covers the given byte offset.
+fn covers(node: &Node<'_>, offset: u32) -> bool {
+    let location = node.location();
+    location.start() <= offset && offset < location.end()
+}
+
+impl ParseResult<'_> {
+    /// Returns the innermost node whose location covers the given byte offset,
+    /// along with its ancestors. A node covers the bytes from its start offset
+    /// up to but not including its end offset. Returns `None` if the offset is
+    /// not covered by the root node.
+    #[must_use]
+    pub fn node_at_offset(&self, offset: u32) -> Option<NodeAt<'_>> {
+        let root = self.node();
+        if !covers(&root, offset) {
+            return None;
+        }
+
+        let mut ancestors = Vec::new();
+        let mut node = root;
+
+        loop {
+            let mut found = None;
+            node.each_child(|child| {
+                if found.is_none() && covers(&child, offset) {
+                    found = Some(child);
+                }
+            });
+
+            match found {
+                Some(child) => ancestors.push(std::mem::replace(&mut node, child)),
+                None => break,
+            }
+        }
+
+        ancestors.reverse();
+        Some(NodeAt { node, ancestors })
+    }
+
+    /// Returns the innermost node that covers the given line and byte column,
+    /// along with its ancestors. The position is converted the same way as
+    /// [`ParseResult::byte_offset`]. Returns `None` if the position is outside
+    /// of the source or is not covered by the root node.
+    #[must_use]
+    pub fn node_at(&self, line: i32, column: u32) -> Option<NodeAt<'_>> {
+        self.node_at_offset(self.byte_offset(line, column)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, parse_with_options, Options};
+
+    #[test]
+    fn test_node_at_offset() {
+        let result = parse(b"foo(bar, baz + 1)");
+
+        let found = result.node_at_offset(9).unwrap();
+        assert_eq!(found.node().location().as_slice(), b"baz");
+        assert!(found.node().as_call_node().is_some());
+
+        let ancestors: Vec<&[u8]> = found.ancestors().iter().map(|node| node.location().as_slice()).collect();
+        assert_eq!(ancestors, [&b"baz + 1"[..], b"bar, baz + 1", b"foo(bar, baz + 1)", b"foo(bar, baz + 1)", b"foo(bar, baz + 1)"]);
+        assert!(found.ancestors().last().unwrap().as_program_node().is_some());
+
+        let found = result.node_at_offset(15).unwrap();
+        assert!(found.node().as_integer_node().is_some());
+    }
+
+    #[test]
+    fn test_node_at_offset_between_children() {
+        let result = parse(b"foo(bar, baz)");
+        let found = result.node_at_offset(7).unwrap();
+        assert_eq!(found.node().location().as_slice(), b"bar, baz");
+    }
+
+    #[test]
+    fn test_node_at_offset_outside() {
+        let result = parse(b"  foo  ");
+        assert!(result.node_at_offset(0).is_none());
+        assert!(result.node_at_offset(5).is_none());
+        assert!(result.node_at_offset(100).is_none());
+    }
+
+    #[test]
+    fn test_node_at() {
+        let result = parse(b"def foo\n  bar(1)\nend\n");
+
+        let (node, ancestors) = result.node_at(2, 6).unwrap().into_parts();
+        assert!(node.as_integer_node().is_some());
+        assert!(ancestors.iter().any(|ancestor| ancestor.as_def_node().is_some()));
+
+        assert!(result.node_at(2, 2).unwrap().node().as_call_node().is_some());
+        assert!(result.node_at(1, 8).is_none());
+        assert!(result.node_at(2, 20).is_none());
+        assert!(result.node_at(0, 0).is_none());
+        assert!(result.node_at(10, 0).is_none());
+    }
+
+    #[test]
+    fn test_node_at_with_start_line() {
+        let options = Options::default().line(10).build();
+        let result = parse_with_options(b"foo\nbar\n", &options);
+
+        assert_eq!(result.node_at(11, 1).unwrap().node().location().as_slice(), b"bar");
+        assert!(result.node_at(1, 0).is_none());
+    }
+}
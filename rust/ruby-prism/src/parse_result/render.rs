@@ -0,0 +1,187 @@
+//! Rendering of diagnostics as annotated source snippets.
+
+use std::fmt::Write;
+
+use super::line_index::find_line;
+use super::{Diagnostic, ParseResult};
+
+/// The ANSI escape sequences used when rendering with color.
+struct Style {
+    enabled: bool,
+}
+
+impl Style {
+    /// Wraps the given text in the given ANSI style, if color is enabled.
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.enabled {
+            format!("\x1b[{code}m{text}\x1b[0m")
+        } else {
+            text.to_string()
+        }
+    }
+}
+
+/// Returns the whitespace that lines up a caret underneath the end of the
+/// given prefix, preserving tabs so that it lines up in the terminal too.
+fn padding(prefix: &[u8]) -> String {
+    String::from_utf8_lossy(prefix).chars().map(|character| if character == '\t' { '\t' } else { ' ' }).collect()
+}
+
+/// Returns the number of characters in the given bytes.
+fn width(bytes: &[u8]) -> usize {
+    String::from_utf8_lossy(bytes).chars().count()
+}
+
+impl Diagnostic<'_> {
+    /// Renders the diagnostic in the style of `rustc`, with a header that
+    /// contains the message, the file path and position from the parse
+    /// options, and every source line the diagnostic spans with the offending
+    /// range underlined. If `color` is true, ANSI escape sequences are used to
+    /// highlight the output.
+    #[must_use]
+    pub fn render(&self, result: &ParseResult<'_>, color: bool) -> String {
+        let style = Style { enabled: color };
+        let (label, code) = if self.error_level().is_some() { ("error", "1;31") } else { ("warning", "1;33") };
+
+        let source = result.source();
+        let offsets = result.line_offsets();
+        let location = self.location();
+
+        let start = location.start() as usize;
+        let end = location.end() as usize;
+
+        // A location that ends with a newline should not underline the
+        // following line, so we look up the line of the last byte instead.
+        let first = find_line(offsets, location.start());
+        let last = if end > start { find_line(offsets, location.end() - 1) } else { first };
+
+        let line_bounds = |index: usize| {
+            let line_start = offsets[index] as usize;
+            let line_end = offsets.get(index + 1).map_or(source.len(), |&offset| offset as usize);
+            let line = &source[line_start..line_end];
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            (line_start, line.strip_suffix(b"\r").unwrap_or(line))
+        };
+
+        let first_number = location.start_line();
+        let last_number = first_number.saturating_add(i32::try_from(last - first).unwrap_or(i32::MAX));
+        let gutter = " ".repeat(last_number.to_string().len());
+
+        let (first_start, first_line) = line_bounds(first);
+        let column = width(&first_line[..(start - first_start).min(first_line.len())]) + 1;
+
+        let filepath = result.filepath();
+        let position = if filepath.is_empty() { format!("{first_number}:{column}") } else { format!("{}:{first_number}:{column}", String::from_utf8_lossy(filepath)) };
+
+        let mut output = format!("{}{}\n", style.paint(code, &format!("{label}[{}]", self.type_name())), style.paint("1", &format!(": {}", self.message())));
+        let _ = writeln!(output, "{gutter}{} {position}", style.paint("1;34", "-->"));
+        let _ = writeln!(output, "{gutter} {}", style.paint("1;34", "|"));
+
+        for (index, number) in (first..=last).zip(first_number..) {
+            let (line_start, line) = line_bounds(index);
+            let number = format!("{number:>width$} |", width = gutter.len());
+            let _ = writeln!(output, "{} {}", style.paint("1;34", &number), String::from_utf8_lossy(line));
+
+            let underline_start = if index == first { (start - line_start).min(line.len()) } else { 0 };
+            let underline_end = if index == last { end.saturating_sub(line_start).min(line.len()) } else { line.len() };
+
+            // Empty locations and locations at the end of a line still get a
+            // single caret so that they can be seen.
+            let mut carets = width(&line[underline_start..underline_end.max(underline_start)]);
+            if index == first {
+                carets = carets.max(1);
+            }
+
+            let _ = write!(output, "{gutter} {}", style.paint("1;34", "|"));
+            if carets > 0 {
+                let _ = write!(output, " {}{}", padding(&line[..underline_start]), style.paint(code, &"^".repeat(carets)));
+            }
+            output.push('\n');
+        }
+
+        output
+    }
+}
+
+impl ParseResult<'_> {
+    /// Renders every error followed by every warning in the parse result using
+    /// [`Diagnostic::render`], separated by blank lines.
+    #[must_use]
+    pub fn format_diagnostics(&self, color: bool) -> String {
+        let rendered: Vec<String> = self.errors().chain(self.warnings()).map(|diagnostic| diagnostic.render(self, color)).collect();
+        rendered.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{parse, parse_with_options, Options};
+
+    #[test]
+    fn test_render_single_line() {
+        let options = Options::default().filepath("test.rb").build();
+        let result = parse_with_options(b"alias $a $1\n", &options);
+        let error = result.errors().next().unwrap();
+
+        let expected = "\
+error[alias_argument_numbered_reference]: invalid argument being passed to `alias`; can't make alias for the number variables
+ --> test.rb:1:10
+  |
+1 | alias $a $1
+  |          ^^
+";
+        assert_eq!(error.render(&result, false), expected);
+    }
+
+    #[test]
+    fn test_render_without_filepath() {
+        let result = parse(b"foo +1");
+        let warning = result.warnings().next().unwrap();
+        let rendered = warning.render(&result, false);
+
+        assert!(rendered.starts_with("warning[ambiguous_first_argument_plus]: "));
+        assert!(rendered.contains(" --> 1:5\n"));
+        assert!(rendered.ends_with("1 | foo +1\n  |     ^\n"));
+    }
+
+    #[test]
+    fn test_render_unterminated_def() {
+        let result = parse(b"def foo");
+
+        let expected = "\
+error[def_params_term]: expected a delimiter to close the parameters
+ --> 1:8
+  |
+1 | def foo
+  |        ^
+
+error[unexpected_token_close_context]: unexpected end-of-input, assuming it is closing the parent top level context
+ --> 1:8
+  |
+1 | def foo
+  |        ^
+
+error[def_term]: expected an `end` to close the `def` statement
+ --> 1:1
+  |
+1 | def foo
+  | ^^^
+";
+        assert_eq!(result.format_diagnostics(false), expected);
+    }
+
+    #[test]
+    fn test_render_with_color() {
+        let result = parse(b"alias $a $1");
+        let rendered = result.format_diagnostics(true);
+
+        assert!(rendered.starts_with("\x1b[1;31merror[alias_argument_numbered_reference]\x1b[0m"));
+        assert!(rendered.contains("\x1b[1;31m^^\x1b[0m"));
+    }
+
+    #[test]
+    fn test_format_diagnostics_success() {
+        let result = parse(b"1 + 1");
+        assert!(result.format_diagnostics(false).is_empty());
+    }
+}
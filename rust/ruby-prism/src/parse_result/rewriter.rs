@@ -0,0 +1,285 @@
+//! Rewriting the source of a parse result through a list of edits.
+//!
+//! Edits are recorded against the original source and checked for conflicts
+//! as they are added, so that they can be applied in one pass to produce the
+//! new source, or handed to an editor as text edits.
+
+use std::fmt;
+use std::ops::Range;
+
+use super::{CodeUnits, Location, ParseResult};
+use crate::Node;
+
+/// An error returned when an edit cannot be added to a [`Rewriter`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewriteError {
+    /// The edit overlaps an edit that was already added. Insertions only
+    /// conflict with replacements that span the point they insert at.
+    Overlap {
+        /// The byte range of the edit that was already added.
+        existing: Range<u32>,
+        /// The byte range of the edit that was rejected.
+        rejected: Range<u32>,
+    },
+    /// The location or node came from a different parse result.
+    ForeignLocation,
+}
+
+impl fmt::Display for RewriteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Overlap { existing, rejected } => {
+                write!(f, "Edit at {rejected:?} overlaps an existing edit at {existing:?}")
+            },
+            Self::ForeignLocation => {
+                write!(f, "Edit location does not belong to the rewritten parse result")
+            },
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+/// A position in the source, counted the way the Language Server Protocol
+/// counts them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Position {
+    /// The line, starting from 0 regardless of the start line in the parse
+    /// options.
+    pub line: u32,
+
+    /// The column, in the code units that the edits were requested in.
+    pub character: u32,
+}
+
+/// A change to the source, in the shape of a Language Server Protocol
+/// `TextEdit`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextEdit {
+    /// The range of the original source to replace.
+    pub range: Range<Position>,
+
+    /// The text to replace the range with. Bytes that are not valid UTF-8
+    /// are replaced with the replacement character.
+    pub new_text: String,
+}
+
+/// A single edit to the original source.
+#[derive(Debug)]
+struct Edit {
+    start: u32,
+    end: u32,
+    text: Vec<u8>,
+}
+
+/// Collects edits to the source of a parse result and applies them.
+#[derive(Debug)]
+pub struct Rewriter<'pr> {
+    result: &'pr ParseResult<'pr>,
+    edits: Vec<Edit>,
+}
+
+impl<'pr> Rewriter<'pr> {
+    /// Creates a rewriter with no edits for the given parse result.
+    #[must_use]
+    pub const fn new(result: &'pr ParseResult<'pr>) -> Self {
+        Rewriter { result, edits: Vec::new() }
+    }
+
+    /// Replaces the source covered by the given location with the given text.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RewriteError`] if the location overlaps an existing edit or
+    /// comes from a different parse result.
+    pub fn replace(&mut self, location: &Location<'_>, text: impl AsRef<[u8]>) -> Result<(), RewriteError> {
+        if location.parser != self.result.parser.cast_const() {
+            return Err(RewriteError::ForeignLocation);
+        }
+
+        self.edit(location.start(), location.end(), text.as_ref())
+    }
+
+    /// Inserts the given text just before the given node.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RewriteError`] if the insertion falls inside an existing
+    /// replacement or the node comes from a different parse result.
+    pub fn insert_before(&mut self, node: &Node<'_>, text: impl AsRef<[u8]>) -> Result<(), RewriteError> {
+        let start = self.node_location(node)?.start();
+        self.edit(start, start, text.as_ref())
+    }
+
+    /// Inserts the given text just after the given node.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RewriteError`] if the insertion falls inside an existing
+    /// replacement or the node comes from a different parse result.
+    pub fn insert_after(&mut self, node: &Node<'_>, text: impl AsRef<[u8]>) -> Result<(), RewriteError> {
+        let end = self.node_location(node)?.end();
+        self.edit(end, end, text.as_ref())
+    }
+
+    /// Removes the source of the given node.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`RewriteError`] if the node overlaps an existing edit or comes
+    /// from a different parse result.
+    pub fn remove(&mut self, node: &Node<'_>) -> Result<(), RewriteError> {
+        let location = self.node_location(node)?;
+        self.edit(location.start(), location.end(), &[])
+    }
+
+    /// Returns the location of the given node, checking that it belongs to
+    /// the rewritten parse result.
+    fn node_location<'a>(&self, node: &Node<'a>) -> Result<Location<'a>, RewriteError> {
+        if node.parser() == self.result.parser.cast_const() {
+            Ok(node.location())
+        } else {
+            Err(RewriteError::ForeignLocation)
+        }
+    }
+
+    /// Records an edit after checking it against the existing edits.
+    fn edit(&mut self, start: u32, end: u32, text: &[u8]) -> Result<(), RewriteError> {
+        if let Some(existing) = self.edits.iter().find(|edit| edit.start < end && start < edit.end) {
+            return Err(RewriteError::Overlap {
+                existing: existing.start..existing.end,
+                rejected: start..end,
+            });
+        }
+
+        self.edits.push(Edit { start, end, text: text.to_vec() });
+        Ok(())
+    }
+
+    /// Returns true if no edits have been added.
+    #[must_use]
+    pub const fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// Returns the edits in the order to apply them: by position in the
+    /// source, and in the order they were added for insertions at the same
+    /// point.
+    fn sorted(&self) -> Vec<&Edit> {
+        let mut edits: Vec<&Edit> = self.edits.iter().collect();
+        edits.sort_by_key(|edit| (edit.start, edit.end));
+        edits
+    }
+
+    /// Returns the source with every edit applied.
+    #[must_use]
+    pub fn rewrite(&self) -> Vec<u8> {
+        let source = self.result.source();
+        let mut rewritten = Vec::with_capacity(source.len());
+        let mut cursor = 0;
+
+        for edit in self.sorted() {
+            rewritten.extend_from_slice(&source[cursor..edit.start as usize]);
+            rewritten.extend_from_slice(&edit.text);
+            cursor = edit.end as usize;
+        }
+
+        rewritten.extend_from_slice(&source[cursor..]);
+        rewritten
+    }
+
+    /// Returns the edits as Language Server Protocol text edits against the
+    /// original source, with columns counted in the given code units. Editors
+    /// count in UTF-16 code units unless they negotiate otherwise.
+    #[must_use]
+    pub fn text_edits(&self, units: CodeUnits) -> Vec<TextEdit> {
+        let index = self.result.line_index();
+        let start_line = index.line(0);
+        let position = |offset: u32| Position {
+            line: u32::try_from(index.line(offset) - start_line).unwrap_or(u32::MAX),
+            character: index.code_units_column(offset, units),
+        };
+
+        self.sorted()
+            .into_iter()
+            .map(|edit| TextEdit {
+                range: position(edit.start)..position(edit.end),
+                new_text: String::from_utf8_lossy(&edit.text).into_owned(),
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Position, RewriteError, Rewriter};
+    use crate::{parse, CodeUnits};
+
+    #[test]
+    fn test_rewrite() {
+        let result = parse(b"foo(bar, baz)\nqux\n");
+        let body = result.node().as_program_node().unwrap().statements().body();
+        let call = body.first().unwrap().as_call_node().unwrap();
+        let arguments: Vec<_> = call.arguments().unwrap().arguments().iter().collect();
+
+        let mut rewriter = Rewriter::new(&result);
+        assert!(rewriter.is_empty());
+
+        rewriter.replace(&call.message_loc().unwrap(), "foo!").unwrap();
+        rewriter.insert_before(&arguments[0], "[").unwrap();
+        rewriter.insert_after(&arguments[1], "]").unwrap();
+        rewriter.insert_before(&arguments[0], "*").unwrap();
+        rewriter.remove(&body.last().unwrap()).unwrap();
+
+        assert!(!rewriter.is_empty());
+        assert_eq!(rewriter.rewrite(), b"foo!([*bar, baz])\n\n");
+    }
+
+    #[test]
+    fn test_overlap() {
+        let result = parse(b"foo(bar, baz)");
+        let call = result.node().as_program_node().unwrap().statements().body().first().unwrap();
+        let arguments = call.as_call_node().unwrap().arguments().unwrap();
+        let bar = arguments.arguments().first().unwrap();
+
+        let mut rewriter = Rewriter::new(&result);
+        rewriter.remove(&arguments.as_node()).unwrap();
+
+        assert_eq!(rewriter.insert_before(&bar, "x"), Ok(()));
+        assert_eq!(rewriter.insert_after(&bar, "x"), Err(RewriteError::Overlap { existing: 4..12, rejected: 7..7 }));
+        assert_eq!(rewriter.replace(&call.location(), "x"), Err(RewriteError::Overlap { existing: 4..12, rejected: 0..13 }));
+        assert!(rewriter.insert_after(&call, "x").is_ok());
+    }
+
+    #[test]
+    fn test_foreign_location() {
+        let result = parse(b"foo");
+        let other = parse(b"foo");
+
+        let mut rewriter = Rewriter::new(&result);
+        assert_eq!(rewriter.remove(&other.node()), Err(RewriteError::ForeignLocation));
+        assert_eq!(rewriter.replace(&other.node().location(), ""), Err(RewriteError::ForeignLocation));
+    }
+
+    #[test]
+    fn test_text_edits() {
+        let result = parse("x = \"😀\"\nfoo(bar)\n".as_bytes());
+        let statements = result.node().as_program_node().unwrap().statements();
+        let call = statements.body().last().unwrap().as_call_node().unwrap();
+        let string = statements.body().first().unwrap().as_local_variable_write_node().unwrap().value();
+
+        let mut rewriter = Rewriter::new(&result);
+        rewriter.replace(&call.message_loc().unwrap(), "baz").unwrap();
+        rewriter.insert_after(&string, ".freeze").unwrap();
+
+        let edits = rewriter.text_edits(CodeUnits::Utf16);
+        assert_eq!(edits.len(), 2);
+        assert_eq!(edits[0].range, Position { line: 0, character: 8 }..Position { line: 0, character: 8 });
+        assert_eq!(edits[0].new_text, ".freeze");
+        assert_eq!(edits[1].range, Position { line: 1, character: 0 }..Position { line: 1, character: 3 });
+        assert_eq!(edits[1].new_text, "baz");
+
+        let edits = rewriter.text_edits(CodeUnits::Utf8);
+        assert_eq!(edits[0].range.start.character, 10);
+    }
+}
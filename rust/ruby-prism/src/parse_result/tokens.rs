@@ -0,0 +1,203 @@
+//! Token handling for the prism lexer.
+
+use std::marker::PhantomData;
+use std::ptr::NonNull;
+
+use ruby_prism_sys::{pm_arena_t, pm_parse, pm_parser_lex_callback_set, pm_parser_lex_state, pm_parser_start, pm_parser_t, pm_token_t};
+
+use super::{Comments, Diagnostics, Location, MagicComments, ParseResult};
+use crate::{Node, TokenKind};
+
+/// A token as it was reported by the lex callback, before it has been tied to
+/// the lifetime of a parse result.
+#[derive(Debug, Clone, Copy)]
+struct RawToken {
+    kind: TokenKind,
+    start: u32,
+    length: u32,
+    lex_state: i32,
+}
+
+// C callback that collects lexed tokens into a Vec. Tokens of a type that
+// these bindings do not know about are skipped, since a panic here would
+// unwind across the FFI boundary.
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+unsafe extern "C" fn collect_token(parser: *mut pm_parser_t, token: *mut pm_token_t, data: *mut std::ffi::c_void) {
+    let vec = &mut *(data.cast::<Vec<RawToken>>());
+    let token = &*token;
+    let Some(kind) = TokenKind::new(token.type_) else {
+        return;
+    };
+    let parser_start = pm_parser_start(parser);
+
+    vec.push(RawToken {
+        kind,
+        start: token.start.offset_from(parser_start) as u32,
+        length: token.end.offset_from(token.start) as u32,
+        lex_state: pm_parser_lex_state(parser),
+    });
+}
+
+/// A token that was found during lexing.
+#[derive(Debug)]
+pub struct Token<'pr> {
+    raw: RawToken,
+    parser: *const pm_parser_t,
+    marker: PhantomData<&'pr [u8]>,
+}
+
+impl<'pr> Token<'pr> {
+    /// Returns the kind of the token.
+    #[must_use]
+    pub const fn kind(&self) -> TokenKind {
+        self.raw.kind
+    }
+
+    /// The location of the token in the source.
+    #[must_use]
+    pub const fn location(&self) -> Location<'pr> {
+        Location {
+            parser: self.parser,
+            start: self.raw.start,
+            length: self.raw.length,
+            marker: PhantomData,
+        }
+    }
+
+    /// Returns the text of the token.
+    #[must_use]
+    pub fn text(&self) -> &'pr [u8] {
+        self.location().as_slice()
+    }
+
+    /// Returns the state of the lexer after this token was lexed. This is a
+    /// bitset matching the values of Ruby's `Ripper::Lex::State`.
+    #[must_use]
+    pub const fn lex_state(&self) -> i32 {
+        self.raw.lex_state
+    }
+}
+
+/// An iterator over tokens collected from the lex result.
+pub struct Tokens<'pr> {
+    raw: &'pr [RawToken],
+    index: usize,
+    parser: *const pm_parser_t,
+}
+
+impl<'pr> Tokens<'pr> {
+    const fn new(raw: &'pr [RawToken], parser: *const pm_parser_t) -> Self {
+        Tokens { raw, index: 0, parser }
+    }
+}
+
+impl<'pr> Iterator for Tokens<'pr> {
+    type Item = Token<'pr>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index < self.raw.len() {
+            let raw = self.raw[self.index];
+            self.index += 1;
+            Some(Token { raw, parser: self.parser, marker: PhantomData })
+        } else {
+            None
+        }
+    }
+}
+
+/// The result of lexing a source string.
+#[derive(Debug)]
+pub struct LexResult<'pr> {
+    result: ParseResult<'pr>,
+    tokens: Vec<RawToken>,
+}
+
+impl<'pr> LexResult<'pr> {
+    pub(crate) unsafe fn new(source: &'pr [u8], arena: *mut pm_arena_t, parser: *mut pm_parser_t) -> Self {
+        let mut tokens: Vec<RawToken> = Vec::new();
+
+        pm_parser_lex_callback_set(parser, Some(collect_token), (&raw mut tokens).cast());
+        let node = NonNull::new_unchecked(pm_parse(parser));
+        pm_parser_lex_callback_set(parser, None, std::ptr::null_mut());
+
+        LexResult {
+            result: ParseResult::new(source, arena, parser, node),
+            tokens,
+        }
+    }
+
+    /// Returns the parse result, which holds the AST as well as the comments
+    /// and diagnostics. Lexing runs the full parser, so the tree is always
+    /// available.
+    #[must_use]
+    pub const fn result(&self) -> &ParseResult<'pr> {
+        &self.result
+    }
+
+    /// Returns the root node of the parse result.
+    #[must_use]
+    pub fn node(&self) -> Node<'_> {
+        self.result.node()
+    }
+
+    /// Returns the source string that was lexed.
+    #[must_use]
+    pub const fn source(&self) -> &'pr [u8] {
+        self.result.source()
+    }
+
+    /// Returns an iterator that can be used to iterate over the tokens in the
+    /// lex result, in the order in which they were lexed.
+    #[must_use]
+    pub fn tokens(&self) -> Tokens<'_> {
+        Tokens::new(&self.tokens, self.result.parser)
+    }
+
+    /// Returns an iterator that can be used to iterate over the errors in the
+    /// lex result.
+    #[must_use]
+    pub fn errors(&self) -> Diagnostics<'_> {
+        self.result.errors()
+    }
+
+    /// Returns an iterator that can be used to iterate over the warnings in the
+    /// lex result.
+    #[must_use]
+    pub fn warnings(&self) -> Diagnostics<'_> {
+        self.result.warnings()
+    }
+
+    /// Returns an iterator that can be used to iterate over the comments in the
+    /// lex result.
+    #[must_use]
+    pub fn comments(&self) -> Comments<'_> {
+        self.result.comments()
+    }
+
+    /// Returns an iterator that can be used to iterate over the magic comments
+    /// in the lex result.
+    #[must_use]
+    pub fn magic_comments(&self) -> MagicComments<'_> {
+        self.result.magic_comments()
+    }
+
+    /// Returns an optional location of the __END__ marker and the rest of the content of the file.
+    #[must_use]
+    pub fn data_loc(&self) -> Option<Location<'_>> {
+        self.result.data_loc()
+    }
+
+    /// Returns true if there were no errors during lexing and false if there
+    /// were.
+    #[must_use]
+    pub fn is_success(&self) -> bool {
+        self.result.is_success()
+    }
+
+    /// Returns true if there were errors during lexing and false if there were
+    /// not.
+    #[must_use]
+    pub fn is_failure(&self) -> bool {
+        self.result.is_failure()
+    }
+}